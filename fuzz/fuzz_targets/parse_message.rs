@@ -0,0 +1,71 @@
+//! Feeds arbitrary messages to the parser, which must not panic whatever users write, and checks
+//! that what it parses formats back into the same request. Run with `cargo fuzz run parse_message`.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate discord;
+extern crate discord_sh_bot;
+extern crate time;
+extern crate chrono_tz;
+
+use chrono_tz::Tz;
+use discord::model::OnlineStatus;
+use discord_sh_bot::common::{Token, Tokenizer, Tokens};
+use discord_sh_bot::message_formatter::format_request;
+use discord_sh_bot::message_parser::{parse_command, suggest};
+use discord_sh_bot::model::{Request, UserData};
+use discord_sh_bot::replier;
+use discord_sh_bot::vocabulary::{Vocabulary, Language};
+
+const TIMEZONES: [Tz; 4] = [Tz::UTC, Tz::Europe__Berlin, Tz::America__New_York, Tz::Asia__Kolkata];
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the timezone and language, the rest is the message.
+    let (first, content) = match data.split_first() {
+        Some((&first, content)) => (first, content),
+        None => return,
+    };
+    let content = match ::std::str::from_utf8(content) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+    let tz = TIMEZONES[first as usize % TIMEZONES.len()];
+    let vocabulary = if first & 0x80 == 0 {
+        Vocabulary::new(Language::English)
+    } else {
+        Vocabulary::new(Language::German)
+    };
+    // 2016-09-27 18:13:20 UTC
+    let now = time::at_utc(time::Timespec::new(1475000000, 0));
+
+    // The tokens up to a quote that isn't closed, like the parser sees them.
+    let tokenize = |text| Tokenizer::new(text).filter_map(Result::ok).collect::<Vec<Token>>();
+    let tokenized = tokenize(content);
+    let mut tokens = Tokens::new(content, &tokenized);
+    loop {
+        let remaining = tokens.clone().collect::<Vec<&str>>();
+        let rest_tokens = tokens.rest().map_or(Vec::new(), |rest| tokenize(rest));
+        assert_eq!(remaining,
+                   rest_tokens.iter().map(|token| &*token.text).collect::<Vec<&str>>());
+        if tokens.next().is_none() {
+            break;
+        }
+    }
+
+    let request = parse_command(content, now, tz, &vocabulary).request;
+    if let Some(formatted) = format_request(&request, &now, tz, &vocabulary) {
+        assert_eq!(request, parse_command(&formatted, now, tz, &vocabulary).request);
+    }
+    match request {
+        Request::Invalid { position, .. } => assert!(content.is_char_boundary(position)),
+        Request::Want { time, wants } => {
+            let mut ud = UserData::new(OnlineStatus::Online);
+            ud.timezone = Some(tz);
+            ud.time_wants.insert(time, wants);
+            replier::want(&ud, &now, Some(content));
+        }
+        _ => {}
+    }
+    suggest(content, now, tz, &vocabulary);
+});
@@ -0,0 +1,529 @@
+use discord::model::{UserId, ServerId};
+use chrono_tz::Tz;
+use time;
+use common::Tokens;
+use message_formatter;
+use message_parser::{self, ParseContext};
+use model::{Command, Request, Reply, Timeframe, TimeframeKind, Tier, Want};
+use replier;
+use sh_status::ShStatus;
+use vocabulary::{Vocabulary, Keyword, Language};
+use DEFAULT_PREFIX;
+
+/// Who may use a command.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum Permission {
+    Anyone,
+    /// Anyone may use the command without arguments (to show a setting), but in a server only its
+    /// owner and members who may manage it may use it with arguments (to change the setting).
+    ManageServerForArguments,
+}
+
+/// A table of what an argument of a command can be, shown in the command's help. `Timeframes`,
+/// `TimePhrases` and `TimeframeKinds` are the tables the parser uses, so they can't drift from
+/// what's understood. `Tiers` and `ValueFormats` only list examples, which are tested to parse.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ArgumentTable {
+    /// The ways tiers can be given, see `message_parser::TIER_FORMS`.
+    Tiers,
+    /// See `message_parser::TIMEFRAMES`.
+    Timeframes,
+    /// How times, durations and days are written, see `message_parser::VALUE_FORMATS`.
+    ValueFormats,
+    /// See `message_parser::TIME_PHRASES`.
+    TimePhrases,
+    /// The kinds of timeframes a dont want can be limited to, see
+    /// `message_parser::TIMEFRAME_KINDS`.
+    TimeframeKinds,
+}
+
+/// Everything there is to know about a command: how it's written, parsed, documented and carried
+/// out. Adding a command means adding its `Command` and `Request` variants, an entry to `COMMANDS`
+/// and how its requests are formatted to `message_formatter::format_request()`.
+pub struct CommandSpec {
+    pub command: Command,
+    /// The keywords the command starts with. They may come in any order, as some languages put
+    /// the negation after the verb (e.g. "will nicht").
+    pub keywords: &'static [Keyword],
+    /// What may follow the keywords, as shown in the help. Parts in brackets are optional, |
+    /// separates alternatives.
+    pub arguments: &'static str,
+    /// One line about the command, shown in the list of all commands.
+    pub summary: &'static str,
+    /// Everything about the command, shown in its own help.
+    pub description: &'static str,
+    /// What the arguments can be, shown in the command's help.
+    pub tables: &'static [ArgumentTable],
+    /// Returns the requests to show the command with in its help. They're shown as the commands
+    /// that canonically ask for them, see `message_formatter::format_request()`.
+    pub examples: fn() -> Vec<Request>,
+    pub permission: Permission,
+    /// Parses what follows the keywords.
+    pub parse: fn(Tokens, &ParseContext) -> Request,
+    /// Carries out a request returned by `parse`. Returns None for requests of other commands,
+    /// which `parse` doesn't return.
+    pub handle: fn(Request, &mut Context) -> Option<Reply>,
+}
+
+/// What a command is carried out for.
+pub struct Context<'a> {
+    pub sh_status: &'a mut ShStatus,
+    pub user_id: UserId,
+    /// The server the message was sent in, None for private channels.
+    pub server_id: Option<ServerId>,
+    pub vocabulary: &'a Vocabulary,
+    /// The time the message was parsed at.
+    pub now: time::Tm,
+}
+
+impl<'a> Context<'a> {
+    /// Returns the command that canonically asks for the request, as the user would have to type
+    /// it (i.e. with the server's prefix), or None if it can't be formatted.
+    fn command_text(&self, request: &Request) -> Option<String> {
+        let tz = self.sh_status.get_user_timezone(self.user_id).unwrap_or(Tz::UTC);
+        let command = message_formatter::format_request(request, &self.now, tz, self.vocabulary);
+        match (command, self.server_id) {
+            (Some(command), Some(server_id)) => {
+                let prefix = self.sh_status
+                    .get_guild_prefix(server_id)
+                    .unwrap_or(DEFAULT_PREFIX);
+                Some(format!("{} {}", prefix, command))
+            }
+            (command, None) => command,
+            (None, _) => None,
+        }
+    }
+}
+
+/// All commands, in the order they're listed in the help.
+pub static COMMANDS: [CommandSpec; 9] =
+    [CommandSpec {
+         command: Command::Help,
+         keywords: &[Keyword::Help],
+         arguments: "[<command>]",
+         summary: "Shows the commands, or how to use one of them.",
+         description: "Shows all commands, or everything about using the given one.",
+         tables: &[],
+         examples: help_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_help,
+         handle: handle_help,
+     },
+     CommandSpec {
+         command: Command::Want,
+         keywords: &[Keyword::Want],
+         arguments: "[<tiers>] [<timeframe>]",
+         summary: "Notes that you're up for Stronghold.",
+         description: "Notes that you're up for Stronghold, in the given tiers (all if none are \
+                       given) and timeframe (until you log out if none is given).",
+         tables: &[ArgumentTable::Tiers,
+                   ArgumentTable::Timeframes,
+                   ArgumentTable::ValueFormats,
+                   ArgumentTable::TimePhrases],
+         examples: want_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_want,
+         handle: handle_want,
+     },
+     CommandSpec {
+         command: Command::DontWant,
+         keywords: &[Keyword::Dont, Keyword::Want],
+         arguments: "[<tiers>] [<timeframe kinds>]",
+         summary: "Takes you off the list.",
+         description: "Takes you off the list, only for the given tiers and kinds of timeframes \
+                       if any are given.",
+         tables: &[ArgumentTable::Tiers, ArgumentTable::TimeframeKinds],
+         examples: dont_want_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_dont_want,
+         handle: handle_dont_want,
+     },
+     CommandSpec {
+         command: Command::Status,
+         keywords: &[Keyword::Status],
+         arguments: "",
+         summary: "Shows who is up for Stronghold.",
+         description: "Lists the players who are up for Stronghold in each tier, whether \
+                       they're online or idle and until when, the ones who leave first first.",
+         tables: &[],
+         examples: status_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_status,
+         handle: handle_status,
+     },
+     CommandSpec {
+         command: Command::Me,
+         keywords: &[Keyword::Me],
+         arguments: "",
+         summary: "Shows what I've noted about you.",
+         description: "Shows what I've noted about you: the tiers you're up for and until when, \
+                       whether I see you as online and your timezone, with how to change them.",
+         tables: &[],
+         examples: me_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_me,
+         handle: handle_me,
+     },
+     CommandSpec {
+         command: Command::Schedules,
+         keywords: &[Keyword::Schedules],
+         arguments: "[remove <number>]",
+         summary: "Lists or removes your weekly schedules.",
+         description: "Lists your weekly schedules or removes one of them, by its number in the \
+                       list.",
+         tables: &[],
+         examples: schedules_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_schedules,
+         handle: handle_schedules,
+     },
+     CommandSpec {
+         command: Command::Timezone,
+         keywords: &[Keyword::Timezone],
+         arguments: "[<name from the tz database>]",
+         summary: "Shows or sets your timezone.",
+         description: "Shows or sets the timezone your times are in.",
+         tables: &[],
+         examples: timezone_examples,
+         permission: Permission::Anyone,
+         parse: message_parser::parse_timezone,
+         handle: handle_timezone,
+     },
+     CommandSpec {
+         command: Command::Prefix,
+         keywords: &[Keyword::Prefix],
+         arguments: "[<new prefix>]",
+         summary: "Shows or sets the prefix in this server.",
+         description: "Shows or sets the prefix messages in this server start with. Only server \
+                       managers can set it.",
+         tables: &[],
+         examples: prefix_examples,
+         permission: Permission::ManageServerForArguments,
+         parse: message_parser::parse_prefix,
+         handle: handle_prefix,
+     },
+     CommandSpec {
+         command: Command::Language,
+         keywords: &[Keyword::Language],
+         arguments: "[English | Deutsch]",
+         summary: "Shows or sets the language in this server.",
+         description: "Shows or sets the language of the commands in this server. Only server \
+                       managers can set it.",
+         tables: &[],
+         examples: language_examples,
+         permission: Permission::ManageServerForArguments,
+         parse: message_parser::parse_language,
+         handle: handle_language,
+     }];
+
+impl CommandSpec {
+    /// The name of the command in the vocabulary's language, e.g. "dont want".
+    pub fn name(&self, vocabulary: &Vocabulary) -> String {
+        self.keywords
+            .iter()
+            .map(|&keyword| vocabulary.word(keyword))
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// How the command is written in the vocabulary's language, e.g. "schedules [remove
+    /// <number>]". Keywords in the arguments (e.g. "remove") are translated as well, aliases
+    /// (e.g. "tz") aren't taken as keywords there.
+    pub fn usage(&self, vocabulary: &Vocabulary) -> String {
+        let english = Vocabulary::new(Language::English);
+        let arguments = self.arguments
+            .split(' ')
+            .map(|part| {
+                let word = part.trim_matches(|c: char| !c.is_alphabetic());
+                match english.keyword(word) {
+                    Some(keyword) if english.word(keyword) == word => {
+                        part.replacen(word, vocabulary.word(keyword), 1)
+                    }
+                    _ => part.to_owned(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        match &*arguments {
+            "" => self.name(vocabulary),
+            arguments => format!("{} {}", self.name(vocabulary), arguments),
+        }
+    }
+
+    /// The examples, as the commands that ask for them in the vocabulary's language.
+    pub fn example_commands(&self, vocabulary: &Vocabulary) -> Vec<String> {
+        // None of the examples depend on the time or timezone.
+        let now = time::now_utc();
+        (self.examples)()
+            .iter()
+            .filter_map(|request| {
+                message_formatter::format_request(request, &now, Tz::UTC, vocabulary)
+            })
+            .collect()
+    }
+}
+
+pub fn spec(command: Command) -> &'static CommandSpec {
+    COMMANDS.iter()
+        .find(|spec| spec.command == command)
+        .expect("Every command has a spec.")
+}
+
+fn help_examples() -> Vec<Request> {
+    vec![Request::Help(None),
+         Request::Help(Some(Command::Want)),
+         Request::Help(Some(Command::DontWant))]
+}
+
+fn want_examples() -> Vec<Request> {
+    let want = |tiers: &[Tier], time| {
+        Request::Want {
+            time: time,
+            wants: tiers.iter().map(|&tier| Want { tier: tier }).collect(),
+        }
+    };
+    vec![want(&[Tier::Tier8, Tier::Tier10], Timeframe::UntilLogout),
+         want(&[Tier::Tier10],
+              Timeframe::Weekly {
+                  days: 0b0010101,
+                  from: 19 * 60,
+                  until: 22 * 60,
+              }),
+         want(&[Tier::Tier6], Timeframe::Always)]
+}
+
+fn dont_want_examples() -> Vec<Request> {
+    let dont_want = |tiers: &[Tier], kinds: &[TimeframeKind]| {
+        Request::DontWant {
+            tiers: tiers.iter().cloned().collect(),
+            kinds: kinds.iter().cloned().collect(),
+        }
+    };
+    vec![dont_want(&[], &[]),
+         dont_want(&[Tier::Tier10], &[]),
+         dont_want(&[Tier::Tier6, Tier::Tier8], &[TimeframeKind::Weekly])]
+}
+
+fn status_examples() -> Vec<Request> {
+    vec![Request::Status]
+}
+
+fn me_examples() -> Vec<Request> {
+    vec![Request::ShowMe]
+}
+
+fn schedules_examples() -> Vec<Request> {
+    vec![Request::ListSchedules, Request::RemoveSchedule(2)]
+}
+
+fn timezone_examples() -> Vec<Request> {
+    vec![Request::ShowTimezone, Request::SetTimezone(Tz::Europe__Berlin)]
+}
+
+fn prefix_examples() -> Vec<Request> {
+    vec![Request::ShowPrefix, Request::SetPrefix("!sh".to_owned())]
+}
+
+fn language_examples() -> Vec<Request> {
+    vec![Request::ShowLanguage, Request::SetLanguage(Language::German)]
+}
+
+fn handle_help(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::Help(None) => Some(replier::help(ctx.vocabulary)),
+        Request::Help(Some(command)) => Some(replier::command_help(command, ctx.vocabulary)),
+        _ => None,
+    }
+}
+
+fn handle_want(request: Request, ctx: &mut Context) -> Option<Reply> {
+    let command = ctx.command_text(&request);
+    match request {
+        Request::Want { time, wants } => {
+            let ud = ctx.sh_status.set_user_wants_sh(ctx.user_id, time, wants);
+            Some(replier::want(ud, &ctx.now, command.as_ref().map(|c| &**c)))
+        }
+        _ => None,
+    }
+}
+
+fn handle_dont_want(request: Request, ctx: &mut Context) -> Option<Reply> {
+    let command = ctx.command_text(&request);
+    match request {
+        Request::DontWant { tiers, kinds } => {
+            let ud = ctx.sh_status.set_user_doesnt_want_sh(ctx.user_id, &tiers, &kinds);
+            Some(replier::dont_want(ud, &ctx.now, command.as_ref().map(|c| &**c)))
+        }
+        _ => None,
+    }
+}
+
+fn handle_status(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::Status => {
+            let tz = ctx.sh_status.get_user_timezone(ctx.user_id).unwrap_or(Tz::UTC);
+            Some(replier::status(&ctx.sh_status.get_current_status(), &ctx.now, tz))
+        }
+        _ => None,
+    }
+}
+
+fn handle_me(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::ShowMe => {
+            Some(replier::me(ctx.sh_status.get_user_data(ctx.user_id), &ctx.now, ctx.vocabulary))
+        }
+        _ => None,
+    }
+}
+
+fn handle_schedules(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::ListSchedules => {
+            Some(replier::schedules(ctx.sh_status.get_user_data(ctx.user_id), ctx.vocabulary))
+        }
+        Request::RemoveSchedule(number) => {
+            let tz = ctx.sh_status.get_user_timezone(ctx.user_id).unwrap_or(Tz::UTC);
+            let removed = ctx.sh_status.remove_user_schedule(ctx.user_id, number);
+            Some(replier::schedule_removed(removed, number, tz, ctx.vocabulary))
+        }
+        _ => None,
+    }
+}
+
+fn handle_timezone(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::ShowTimezone => {
+            let tz = ctx.sh_status.get_user_timezone(ctx.user_id);
+            Some(replier::timezone_show(tz, ctx.vocabulary))
+        }
+        Request::SetTimezone(tz) => {
+            Some(replier::timezone_set(ctx.sh_status.set_user_timezone(ctx.user_id, tz)))
+        }
+        _ => None,
+    }
+}
+
+fn handle_prefix(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::ShowPrefix | Request::SetPrefix(_) => {}
+        _ => return None,
+    }
+    let server_id = match ctx.server_id {
+        Some(server_id) => server_id,
+        None => return Some(replier::prefix_private()),
+    };
+    match request {
+        Request::SetPrefix(prefix) => {
+            info!("User {} set the prefix of server {} to {:?}.",
+                  ctx.user_id.0,
+                  server_id.0,
+                  prefix);
+            ctx.sh_status.set_guild_prefix(server_id, prefix.clone());
+            Some(replier::prefix_set(&prefix))
+        }
+        _ => {
+            let prefix = ctx.sh_status.get_guild_prefix(server_id).unwrap_or(DEFAULT_PREFIX);
+            Some(replier::prefix_show(prefix, ctx.vocabulary))
+        }
+    }
+}
+
+fn handle_language(request: Request, ctx: &mut Context) -> Option<Reply> {
+    match request {
+        Request::ShowLanguage | Request::SetLanguage(_) => {}
+        _ => return None,
+    }
+    let server_id = match ctx.server_id {
+        Some(server_id) => server_id,
+        None => return Some(replier::language_private()),
+    };
+    match request {
+        Request::SetLanguage(language) => {
+            info!("User {} set the language of server {} to {:?}.",
+                  ctx.user_id.0,
+                  server_id.0,
+                  language);
+            ctx.sh_status.set_guild_language(server_id, language);
+            Some(replier::language_set(language, &Vocabulary::new(language)))
+        }
+        _ => Some(replier::language_show(ctx.vocabulary.language())),
+    }
+}
+
+#[cfg(test)]
+mod tests_commands {
+    use super::{COMMANDS, spec};
+    use message_formatter::format_request;
+    use message_parser::parse_command;
+    use model::Command;
+    use vocabulary::{Vocabulary, Keyword, Language};
+    use chrono_tz::Tz;
+    use time;
+
+    #[test]
+    fn every_command_has_one_spec() {
+        let commands = [Command::Help,
+                        Command::Want,
+                        Command::DontWant,
+                        Command::Status,
+                        Command::Me,
+                        Command::Timezone,
+                        Command::Schedules,
+                        Command::Prefix,
+                        Command::Language];
+        for &command in commands.iter() {
+            assert_eq!(command, spec(command).command);
+            assert_eq!(1, COMMANDS.iter().filter(|spec| spec.command == command).count());
+        }
+    }
+
+    #[test]
+    fn every_keyword_starts_one_command() {
+        let keywords = [Keyword::Help,
+                        Keyword::Want,
+                        Keyword::Dont,
+                        Keyword::Status,
+                        Keyword::Me,
+                        Keyword::Timezone,
+                        Keyword::Schedules,
+                        Keyword::Prefix,
+                        Keyword::Language];
+        for &keyword in keywords.iter() {
+            assert_eq!(1,
+                       COMMANDS.iter().filter(|spec| spec.keywords[0] == keyword).count(),
+                       "{:?}",
+                       keyword);
+        }
+    }
+
+    #[test]
+    fn usage_is_translated() {
+        let german = Vocabulary::new(Language::German);
+        assert_eq!("zeitpläne [entfernen <number>]", spec(Command::Schedules).usage(&german));
+        assert_eq!("zeitzone [<name from the tz database>]",
+                   spec(Command::Timezone).usage(&german));
+        assert_eq!("schedules [remove <number>]",
+                   spec(Command::Schedules).usage(&Vocabulary::new(Language::English)));
+    }
+
+    #[test]
+    fn examples_are_valid() {
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        for &language in [Language::English, Language::German].iter() {
+            let vocabulary = Vocabulary::new(language);
+            for spec in COMMANDS.iter() {
+                let examples = (spec.examples)();
+                assert!(!examples.is_empty(), "{:?}", spec.command);
+                for request in examples {
+                    let example = format_request(&request, &now, Tz::UTC, &vocabulary)
+                        .expect("Examples can be formatted.");
+                    let parsed = parse_command(&example, now, Tz::UTC, &vocabulary);
+                    assert_eq!(Some(spec.command), parsed.spec.map(|s| s.command), "{}", example);
+                    assert_eq!(request, parsed.request);
+                }
+                assert_eq!((spec.examples)().len(), spec.example_commands(&vocabulary).len());
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::{HashSet, HashMap};
-use std::cmp::Eq;
+use std::cmp::{Eq, min};
 use std::hash::{Hash, BuildHasher};
+use std::iter::Peekable;
+use std::str::CharIndices;
 
 /// Splits a string at the first whitespace and returns a tuple of the parts, both trimmed.
 pub fn str_head_tail(s: &str) -> (String, String) {
@@ -10,6 +12,31 @@ pub fn str_head_tail(s: &str) -> (String, String) {
     (first, second)
 }
 
+/// Returns the number of single-character insertions, deletions, substitutions and transpositions
+/// of adjacent characters it takes to turn one string into the other, ignoring case.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<char>>();
+    let b = b.to_lowercase().chars().collect::<Vec<char>>();
+    // d[i][j] is the distance between the first i characters of a and the first j of b.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() + 1 {
+        d[i][0] = i;
+    }
+    for j in 0..b.len() + 1 {
+        d[0][j] = j;
+    }
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = min(min(d[i - 1][j] + 1, d[i][j - 1] + 1), d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = min(d[i][j], d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
 pub trait Retain<T> {
     fn retain<F>(&mut self, f: F) where F: Fn(&T) -> bool;
 }
@@ -44,66 +71,159 @@ impl<K, V, S> Retain<K> for HashMap<K, V, S>
     }
 }
 
-pub struct SplitWhitespaceWithRest<'a> {
+/// A token of a string, with quotes and escapes resolved.
+#[derive(PartialEq, Debug)]
+pub struct Token {
+    pub text: String,
+    /// Byte index of the first character of the token in the string (including quotes).
+    pub start: usize,
+    /// Byte index after the last character of the token in the string (including quotes).
+    pub end: usize,
+}
+
+/// Splits a string into tokens at whitespace, except for whitespace in double quotes, so
+/// arguments like `"heavy only"` stay together. The quotes can be anywhere in a token and aren't
+/// part of its text. A backslash escapes a following double quote or backslash, any other
+/// backslash is taken literally.
+///
+/// A quote that isn't closed is an error, with the byte index of the quote as position. Nothing is
+/// returned after an error.
+pub struct Tokenizer<'a> {
+    string: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, (String, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.chars.peek().map_or(false, |&(_, c)| c.is_whitespace()) {
+            self.chars.next();
+        }
+        let start = match self.chars.peek() {
+            Some(&(i, _)) => i,
+            None => return None,
+        };
+        let mut text = String::new();
+        // Byte index of the opening quote, while in quotes.
+        let mut open_quote = None;
+        loop {
+            let (i, c) = match self.chars.peek() {
+                Some(&(_, c)) if c.is_whitespace() && open_quote.is_none() => break,
+                Some(&(i, c)) => (i, c),
+                None => break,
+            };
+            self.chars.next();
+            match c {
+                '"' => open_quote = if open_quote.is_some() { None } else { Some(i) },
+                '\\' => {
+                    match self.chars.peek() {
+                        Some(&(_, escaped)) if escaped == '"' || escaped == '\\' => {
+                            text.push(escaped);
+                            self.chars.next();
+                        }
+                        _ => text.push(c),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if let Some(position) = open_quote {
+            return Some(Err(("Missing closing quote.".to_owned(), position)));
+        }
+        let end = self.chars.peek().map_or(self.string.len(), |&(i, _)| i);
+        Some(Ok(Token {
+            text: text,
+            start: start,
+            end: end,
+        }))
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(string: &'a str) -> Self {
+        Tokenizer {
+            string: string,
+            chars: string.char_indices().peekable(),
+        }
+    }
+}
+
+/// Goes through the tokens of a string (see `Tokenizer`), returning their texts.
+#[derive(Clone)]
+pub struct Tokens<'a> {
     string: &'a str,
-    start_idx: usize,
+    tokens: &'a [Token],
+    /// Index of the next token.
+    next_idx: usize,
 }
 
-impl<'a> Iterator for SplitWhitespaceWithRest<'a> {
+impl<'a> Iterator for Tokens<'a> {
     type Item = &'a str;
 
-    // Invariant: self.start_idx is index of first byte of next token, or first byte after the
-    // string.
     fn next(&mut self) -> Option<&'a str> {
-        if self.start_idx == self.string.len() {
-            return None;
+        let token = self.tokens.get(self.next_idx);
+        if token.is_some() {
+            self.next_idx += 1;
         }
-        // Offset from the start index to the next whitespace.
-        let end_offset = self.string[self.start_idx..]
-            .find(char::is_whitespace)
-            .unwrap_or(self.string.len() - self.start_idx);
-        let end_idx = self.start_idx + end_offset;
-        let next_piece = &self.string[self.start_idx..end_idx];
-        // Offset from the end index to the next non-whitespace (i.e. the beginning of the next
-        // token).
-        let next_start_offset = self.string[end_idx..]
-            .find(|c: char| !c.is_whitespace())
-            .unwrap_or(self.string.len() - end_idx);
-        self.start_idx = end_idx + next_start_offset;
-        Some(next_piece)
+        token.map(|token| &*token.text)
     }
 }
 
-impl<'a> SplitWhitespaceWithRest<'a> {
-    pub fn new(string: &'a str) -> Self {
-        let start_idx = string.find(|c: char| !c.is_whitespace())
-            .unwrap_or(string.len());
-        SplitWhitespaceWithRest {
+impl<'a> Tokens<'a> {
+    /// The tokens have to be the string's, in order.
+    pub fn new(string: &'a str, tokens: &'a [Token]) -> Self {
+        Tokens {
             string: string,
-            start_idx: start_idx,
+            tokens: tokens,
+            next_idx: 0,
         }
     }
 
+    /// Returns the byte index of the start of the next token, or the length of the string if there
+    /// are no more tokens.
+    pub fn next_index(&self) -> usize {
+        self.tokens.get(self.next_idx).map_or(self.string.len(), |token| token.start)
+    }
+
+    /// Returns the string from the start of the next token, or None if there are no more tokens.
     pub fn rest(&self) -> Option<&'a str> {
-        if self.start_idx == self.string.len() {
-            None
-        } else {
-            Some(&self.string[self.start_idx..])
-        }
+        self.tokens.get(self.next_idx).map(|token| &self.string[token.start..])
     }
 
-    /// Rewinds the iterator, so that the next call to next() will return the same token again. The
-    /// exception is the case where the previous call to next() returned None: in that case, the
-    /// next call will return the last token of the string.
+    /// Rewinds, so that the next call to next() will return the same token again. If the previous
+    /// call returned None, the next call will return the last token.
     pub fn rewind(&mut self) {
-        let prev_end_idx = self.string[..self.start_idx]
-            .rfind(|c: char| !c.is_whitespace())
-            .unwrap_or(self.start_idx);
-        let prev_start_idx = self.string[..prev_end_idx]
-            .rfind(char::is_whitespace)
-            .map(|i| i + 1)
-            .unwrap_or(0);
-        self.start_idx = prev_start_idx;
+        if self.next_idx > 0 {
+            self.next_idx -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_edit_distance {
+    use super::edit_distance;
+
+    #[test]
+    fn distances() {
+        let cases = vec![("want", "want", 0),
+                         ("Want", "wANT", 0),
+                         ("wnat", "want", 1),
+                         ("wan", "want", 1),
+                         ("wantt", "want", 1),
+                         ("wamt", "want", 1),
+                         ("alwyas", "always", 1),
+                         ("stauts", "status", 1),
+                         ("hlep", "help", 1),
+                         ("möchet", "möchte", 1),
+                         ("", "want", 4),
+                         ("status", "", 6),
+                         ("until", "for", 5),
+                         ("ca", "abc", 3)];
+        for (a, b, distance) in cases {
+            assert_eq!(distance, edit_distance(a, b), "{} -> {}", a, b);
+            assert_eq!(distance, edit_distance(b, a), "{} -> {}", b, a);
+        }
     }
 }
 
@@ -175,123 +295,219 @@ mod tests_hash_map_retain {
 }
 
 #[cfg(test)]
-mod tests_split_whitespace_with_rest {
-    use super::SplitWhitespaceWithRest;
+mod tests_tokenizer {
+    use super::{Tokenizer, Token, Tokens};
+
+    fn token(text: &str, start: usize, end: usize) -> Result<Token, (String, usize)> {
+        Ok(Token {
+            text: text.to_owned(),
+            start: start,
+            end: end,
+        })
+    }
+
+    fn tokenize(s: &str) -> Vec<Result<Token, (String, usize)>> {
+        Tokenizer::new(s).collect()
+    }
+
+    #[test]
+    fn empty() {
+        assert!(tokenize("").is_empty());
+        assert!(tokenize(" \t\n ").is_empty());
+    }
+
+    #[test]
+    fn mixed_whitespace() {
+        assert_eq!(vec![token("asd", 1, 4),
+                        token("ßDf", 7, 11),
+                        token("x", 12, 13),
+                        token("DfG", 15, 18)],
+                   tokenize(" asd   ßDf x\t DfG\t \t"));
+        // A no-break space and an ideographic space.
+        assert_eq!(vec![token("a", 0, 1), token("b", 3, 4), token("c", 7, 8)],
+                   tokenize("a\u{A0}b\u{3000}c"));
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(vec![token("möchte", 0, 7), token("ß", 8, 10), token("\u{1F600}", 11, 15)],
+                   tokenize("möchte ß \u{1F600}"));
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(vec![token("note", 0, 4), token("heavy only", 5, 17), token("x", 18, 19)],
+                   tokenize("note \"heavy only\" x"));
+        assert_eq!(vec![token("name=Jon Doe", 0, 14)], tokenize("name=\"Jon Doe\""));
+        assert_eq!(vec![token("", 1, 3)], tokenize(" \"\" "));
+        assert_eq!(vec![token(" ß\t", 0, 6)], tokenize("\" ß\t\""));
+    }
 
     #[test]
-    fn next_rest_one_space() {
-        let mut s = SplitWhitespaceWithRest::new("asd ßDf x DfG");
-        assert_eq!(Some("asd ßDf x DfG"), s.rest());
-        assert_eq!(Some("asd"), s.next());
-        assert_eq!(Some("ßDf x DfG"), s.rest());
-        assert_eq!(Some("ßDf"), s.next());
-        assert_eq!(Some("x DfG"), s.rest());
-        assert_eq!(Some("x"), s.next());
-        assert_eq!(Some("DfG"), s.rest());
-        assert_eq!(Some("DfG"), s.next());
-        assert_eq!(None, s.rest());
-        assert_eq!(None, s.next());
-        assert_eq!(None, s.rest());
+    fn escapes() {
+        assert_eq!(vec![token("say \"hi\"", 0, 12)], tokenize(r#""say \"hi\"""#));
+        assert_eq!(vec![token("\"a", 0, 3), token("b\\", 4, 7)], tokenize(r#"\"a b\\"#));
+        assert_eq!(vec![token("\\o/", 0, 3)], tokenize(r#"\o/"#));
+        assert_eq!(vec![token("a\\", 0, 2)], tokenize(r#"a\"#));
     }
 
     #[test]
-    fn next_rest_multi_spaces() {
-        let mut s = SplitWhitespaceWithRest::new("asd   ßDf x\t DfG");
-        assert_eq!(Some("asd   ßDf x\t DfG"), s.rest());
-        assert_eq!(Some("asd"), s.next());
-        assert_eq!(Some("ßDf x\t DfG"), s.rest());
-        assert_eq!(Some("ßDf"), s.next());
-        assert_eq!(Some("x\t DfG"), s.rest());
-        assert_eq!(Some("x"), s.next());
-        assert_eq!(Some("DfG"), s.rest());
-        assert_eq!(Some("DfG"), s.next());
-        assert_eq!(None, s.rest());
-        assert_eq!(None, s.next());
-        assert_eq!(None, s.rest());
+    fn missing_closing_quote() {
+        assert_eq!(vec![token("a", 0, 1), Err(("Missing closing quote.".to_owned(), 3))],
+                   tokenize("a b\"c d"));
+        assert_eq!(vec![token("a", 0, 3), Err(("Missing closing quote.".to_owned(), 4))],
+                   tokenize(r#""a" "b\""#));
     }
 
     #[test]
-    fn next_rest_with_surrounding_space() {
-        let mut s = SplitWhitespaceWithRest::new(" asd   ßDf x\t DfG\t \t");
-        assert_eq!(Some("asd   ßDf x\t DfG\t \t"), s.rest());
-        assert_eq!(Some("asd"), s.next());
-        assert_eq!(Some("ßDf x\t DfG\t \t"), s.rest());
-        assert_eq!(Some("ßDf"), s.next());
-        assert_eq!(Some("x\t DfG\t \t"), s.rest());
-        assert_eq!(Some("x"), s.next());
-        assert_eq!(Some("DfG\t \t"), s.rest());
-        assert_eq!(Some("DfG"), s.next());
-        assert_eq!(None, s.rest());
-        assert_eq!(None, s.next());
-        assert_eq!(None, s.rest());
+    fn tokens() {
+        let string = " want \"10 or 8\"  for\t2h ";
+        let tokenized = Tokenizer::new(string).map(Result::unwrap).collect::<Vec<Token>>();
+        let mut tokens = Tokens::new(string, &tokenized);
+        assert_eq!(1, tokens.next_index());
+        assert_eq!(Some("want \"10 or 8\"  for\t2h "), tokens.rest());
+        assert_eq!(Some("want"), tokens.next());
+        assert_eq!(6, tokens.next_index());
+        assert_eq!(Some("10 or 8"), tokens.next());
+        tokens.rewind();
+        assert_eq!(Some("10 or 8"), tokens.next());
+        assert_eq!(Some("for\t2h "), tokens.rest());
+        assert_eq!(vec!["for", "2h"], tokens.clone().collect::<Vec<&str>>());
+        assert_eq!(Some("for"), tokens.next());
+        assert_eq!(Some("2h"), tokens.next());
+        assert_eq!(24, tokens.next_index());
+        assert_eq!(None, tokens.rest());
+        assert_eq!(None, tokens.next());
+        tokens.rewind();
+        assert_eq!(Some("2h"), tokens.next());
+    }
+}
+
+/// Random strings to check properties of the parsers with.
+#[cfg(test)]
+pub mod tests_properties {
+    use super::{Token, Tokenizer, Tokens};
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    /// How many random strings each property is checked for.
+    pub const ITERATIONS: usize = 5000;
+
+    /// The same seed every run, so failures can be reproduced.
+    pub fn rng() -> XorShiftRng {
+        XorShiftRng::from_seed([0x5348, 0x6f6c, 0x6421, 0x2a2a])
+    }
+
+    /// Returns a random string made of the given words, numbers (some of them huge, some with
+    /// separators like ':' or '.'), random characters and all kinds of whitespace.
+    pub fn random_text<R: Rng>(rng: &mut R, words: &[&str]) -> String {
+        let whitespace = [" ", "  ", "\t", "\n", " \t\r\n", "\u{A0}", "\u{2003}", "\u{3000}"];
+        let separators = [":", ".", ",", "-", "+", "h", "m", "\"", "\\"];
+        let mut text = String::new();
+        for _ in 0..rng.gen_range(0, 8) {
+            if rng.gen() {
+                text.push_str(rng.choose(&whitespace).unwrap());
+            }
+            match rng.gen_range(0, 6) {
+                0 | 1 if !words.is_empty() => text.push_str(rng.choose(words).unwrap()),
+                2 => text.push_str(&rng.gen_range(0, 100).to_string()),
+                3 => text.push_str(&rng.gen::<u64>().to_string()),
+                4 => text.push_str(rng.choose(&separators).unwrap()),
+                _ => {
+                    for _ in 0..rng.gen_range(1, 4) {
+                        text.push(rng.gen::<char>());
+                    }
+                }
+            }
+        }
+        if rng.gen() {
+            text.push_str(rng.choose(&whitespace).unwrap());
+        }
+        text
+    }
+
+    /// Words with quotes and escapes, for the tokenizer.
+    const QUOTED_WORDS: [&'static str; 7] = ["a", "ß", "\u{1F600}", "\"a b\"", "x\"\t\"y", "\\\"",
+                                             "\""];
+
+    /// Returns the tokens of the text up to a quote that isn't closed.
+    fn tokenize(text: &str) -> Vec<Token> {
+        Tokenizer::new(text).filter_map(Result::ok).collect()
     }
 
     #[test]
-    fn rewind_one_space() {
-        let mut s = SplitWhitespaceWithRest::new("asd ßDf x DfG");
-        s.next();
-        s.rewind();
-        assert_eq!(Some("asd"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("ßDf"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("x"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("DfG"), s.next());
-        assert_eq!(None, s.next());
-        s.rewind();
-        s.rewind();
-        s.rewind();
-        s.rewind();
-        assert_eq!(Some("asd"), s.next());
+    fn rest_is_remaining_tokens() {
+        let mut rng = rng();
+        for _ in 0..ITERATIONS {
+            let text = random_text(&mut rng, &QUOTED_WORDS);
+            let tokenized = tokenize(&text);
+            let mut tokens = Tokens::new(&text, &tokenized);
+            loop {
+                let remaining = tokens.clone().collect::<Vec<&str>>();
+                match tokens.rest() {
+                    Some(rest) => {
+                        let rest_tokens = tokenize(rest);
+                        assert_eq!(remaining,
+                                   rest_tokens.iter()
+                                       .map(|token| &*token.text)
+                                       .collect::<Vec<&str>>(),
+                                   "{:?}",
+                                   text);
+                        assert_eq!(rest, &text[tokens.next_index()..]);
+                        assert!(!rest.starts_with(char::is_whitespace), "{:?}", text);
+                    }
+                    None => {
+                        assert!(remaining.is_empty(), "{:?}", text);
+                        assert_eq!(text.len(), tokens.next_index());
+                    }
+                }
+                if tokens.next().is_none() {
+                    break;
+                }
+            }
+        }
     }
 
     #[test]
-    fn rewind_multi_spaces() {
-        let mut s = SplitWhitespaceWithRest::new("asd   ßDf x\t DfG");
-        s.next();
-        s.rewind();
-        assert_eq!(Some("asd"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("ßDf"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("x"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("DfG"), s.next());
-        assert_eq!(None, s.next());
-        s.rewind();
-        s.rewind();
-        s.rewind();
-        s.rewind();
-        assert_eq!(Some("asd"), s.next());
+    fn rewind_returns_same_token() {
+        let mut rng = rng();
+        for _ in 0..ITERATIONS {
+            let text = random_text(&mut rng, &QUOTED_WORDS);
+            let tokenized = tokenize(&text);
+            let mut tokens = Tokens::new(&text, &tokenized);
+            loop {
+                let position = tokens.next_index();
+                let token = match tokens.next() {
+                    Some(token) => token,
+                    None => break,
+                };
+                tokens.rewind();
+                assert_eq!(position, tokens.next_index(), "{:?}", text);
+                assert_eq!(Some(token), tokens.next(), "{:?}", text);
+            }
+        }
     }
 
     #[test]
-    fn rewind_with_surrounding_space() {
-        let mut s = SplitWhitespaceWithRest::new(" asd   ßDf x\t DfG\t \t");
-        s.next();
-        s.rewind();
-        assert_eq!(Some("asd"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("ßDf"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("x"), s.next());
-        s.next();
-        s.rewind();
-        assert_eq!(Some("DfG"), s.next());
-        assert_eq!(None, s.next());
-        s.rewind();
-        s.rewind();
-        s.rewind();
-        s.rewind();
-        assert_eq!(Some("asd"), s.next());
+    fn tokens_span_their_source() {
+        let mut rng = rng();
+        for _ in 0..ITERATIONS {
+            let text = random_text(&mut rng, &["a", "ß", "\"a b\"", "\\\""]);
+            let mut end = 0;
+            for token in Tokenizer::new(&text) {
+                let token = match token {
+                    Ok(token) => token,
+                    Err((_, position)) => {
+                        assert_eq!(Some('"'), text[position..].chars().next(), "{:?}", text);
+                        break;
+                    }
+                };
+                assert!(end <= token.start && token.start < token.end, "{:?}", text);
+                let source = &text[token.start..token.end];
+                if !source.contains(|c| c == '"' || c == '\\') {
+                    assert_eq!(source, token.text);
+                }
+                end = token.end;
+            }
+        }
     }
 }
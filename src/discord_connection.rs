@@ -1,20 +1,34 @@
 extern crate discord;
 
 use std;
-use discord::model::{Event, ChannelId, CurrentUser, Message, Channel};
+use std::sync::mpsc;
+use std::time::Duration;
+use discord::model::{Event, ChannelId, CurrentUser, Message, Channel, ServerId, UserId, Member};
+use model::Reply;
 
 const MAX_RETRIES: u32 = 5;
+/// Maximum number of characters Discord accepts in a single message.
+const MAX_MESSAGE_LEN: usize = 2000;
+/// Maximum number of messages a single reply may be split into.
+const MAX_MESSAGES_PER_REPLY: usize = 5;
+/// Appended to the last message if a reply had to be cut off.
+const TRUNCATION_NOTICE: &'static str = "\n(Reply truncated.)";
+const CODE_FENCE: &'static str = "```";
 
 pub trait DiscordConnection {
-    fn recv_event(&mut self) -> Result<Event, String>;
-    fn send_message(&self, channel: &ChannelId, text: &str, tts: bool) -> Result<Message, String>;
+    fn recv_event(&mut self, timeout: Duration) -> Result<Option<Event>, String>;
+    fn send_message(&self, channel: &ChannelId, text: &str, tts: bool)
+                    -> Result<Vec<Message>, String>;
+    fn send_reply(&self, channel: &ChannelId, reply: &Reply) -> Result<Vec<Message>, String>;
     fn get_channel(&self, channel: ChannelId) -> Result<Channel, String>;
+    fn get_member(&self, server: ServerId, user: UserId) -> Result<Member, String>;
     fn shutdown(self);
 }
 
 pub struct BotConnection {
     discord: discord::Discord,
-    conn: discord::Connection,
+    /// Events from the thread that reads from the gateway.
+    events: mpsc::Receiver<Result<Event, String>>,
 }
 
 impl BotConnection {
@@ -22,8 +36,7 @@ impl BotConnection {
         let d = match discord::Discord::from_bot_token(&token) {
             Ok(d) => d,
             Err(err) => {
-                // TODO log, don't print
-                println!("Error logging in: {}", err);
+                error!("Error logging in: {}", err);
                 std::process::exit(1);
             }
         };
@@ -31,19 +44,36 @@ impl BotConnection {
         let (c, ready_event) = match d.connect() {
             Ok((c, re)) => (c, re),
             Err(err) => {
-                // TODO log, don't print
-                println!("Error connecting: {}", err);
+                error!("Error connecting: {}", err);
                 std::process::exit(1);
             }
         };
         let me = ready_event.user;
+        info!("Connected as {} ({}).", me.username, me.id.0);
+        let (sender, events) = mpsc::channel();
+        std::thread::spawn(move || Self::forward_events(c, sender));
         (BotConnection {
             discord: d,
-            conn: c,
+            events: events,
         },
          me)
     }
 
+    /// Receives events from the gateway and sends them on, until nobody listens anymore. Reading
+    /// blocks until the next event, which is why it has its own thread.
+    fn forward_events(mut conn: discord::Connection,
+                      sender: mpsc::Sender<Result<Event, String>>) {
+        loop {
+            let event = Self::retry(&mut || conn.recv_event());
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+        if let Err(err) = conn.shutdown() {
+            error!("Error shutting down the connection: {}", err);
+        }
+    }
+
     fn retry<R>(f: &mut FnMut() -> Result<R, discord::Error>) -> Result<R, String> {
         Self::retry_n(f, MAX_RETRIES, "Maximum number of retries exceeded.")
     }
@@ -62,12 +92,14 @@ impl BotConnection {
                     discord::Error::RateLimited(millis) => {
                         // Rate limited, sleep the prescribed amount of ms. Don't decrese the number
                         // of tries because this can always be fixed.
+                        debug!("Rate limited, retrying in {} ms.", millis);
                         std::thread::sleep(std::time::Duration::from_millis(millis));
                         Self::retry_n(f, tries, last_err_msg)
                     }
                     _ => {
                         // Some other error, wait a second and hope the cause goes away.
                         // TODO maybe handle other specific cases?
+                        warn!("Request failed, {} tries left: {}", tries - 1, err);
                         std::thread::sleep(std::time::Duration::from_secs(1));
                         Self::retry_n(f, tries - 1, &format!("{}", err))
                     }
@@ -78,25 +110,257 @@ impl BotConnection {
 }
 
 impl DiscordConnection for BotConnection {
-    /// Returns an error message on error.
-    fn recv_event(&mut self) -> Result<Event, String> {
-        // TODO do i have to use a move closure? no way to simply pass a function?
-        Self::retry(&mut move || self.conn.recv_event())
+    /// Waits for the next event for at most the given time. Returns None if there was none, or an
+    /// error message on error.
+    fn recv_event(&mut self, timeout: Duration) -> Result<Option<Event>, String> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => event.map(Some),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                // Don't let the caller spin if it keeps asking.
+                std::thread::sleep(timeout);
+                Err("The thread receiving events has stopped.".to_owned())
+            }
+        }
+    }
+
+    /// Sends the text, split into as many messages as necessary to stay within Discord's message
+    /// length limit. Returns the sent messages in order, or an error message on error.
+    fn send_message(&self, channel: &ChannelId, text: &str, tts: bool)
+                    -> Result<Vec<Message>, String> {
+        let mut sent = Vec::new();
+        for part in split_message(text, MAX_MESSAGE_LEN, MAX_MESSAGES_PER_REPLY) {
+            let msg = try!(Self::retry(&mut || self.discord.send_message(channel, &part, "", tts)));
+            sent.push(msg);
+        }
+        Ok(sent)
     }
 
-    /// Returns an error message on error.
-    fn send_message(&self, channel: &ChannelId, text: &str, tts: bool) -> Result<Message, String> {
-        Self::retry(&mut move || self.discord.send_message(channel, text, "", tts))
+    /// Sends the reply rendered as plain text. The discord library doesn't support sending embeds
+    /// (yet), so this is the best we can do.
+    fn send_reply(&self, channel: &ChannelId, reply: &Reply) -> Result<Vec<Message>, String> {
+        self.send_message(channel, &render_plain_text(reply), false)
     }
 
     fn get_channel(&self, channel: ChannelId) -> Result<Channel, String> {
         Self::retry(&mut move || self.discord.get_channel(channel))
     }
 
+    fn get_member(&self, server: ServerId, user: UserId) -> Result<Member, String> {
+        Self::retry(&mut move || self.discord.get_member(server, user))
+    }
+
+    /// Tells the thread receiving events to stop. It can only notice after the next event, so the
+    /// connection is usually closed by the process exiting.
     fn shutdown(self) {
-        if let Err(err) = self.conn.shutdown() {
-            // TODO log, don't print
-            println!("Error shutting down the connection: {}", err);
+        drop(self.events);
+    }
+}
+
+/// Renders a reply as markdown-formatted text. The title and field names are bold, the footer and
+/// timestamp italic. The colour is ignored.
+fn render_plain_text(reply: &Reply) -> String {
+    let mut lines = Vec::new();
+    if let Some(ref title) = reply.title {
+        lines.push(format!("**{}**", title));
+    }
+    if !reply.text.is_empty() {
+        lines.push(reply.text.clone());
+    }
+    for &(ref name, ref value) in &reply.fields {
+        lines.push(format!("**{}:** {}", name, value));
+    }
+    let timestamp = reply.timestamp
+        .and_then(|t| t.to_utc().strftime("%F %R UTC").ok().map(|f| f.to_string()));
+    match (reply.footer.as_ref(), timestamp) {
+        (Some(footer), Some(timestamp)) => lines.push(format!("*{} | {}*", footer, timestamp)),
+        (Some(footer), None) => lines.push(format!("*{}*", footer)),
+        (None, Some(timestamp)) => lines.push(format!("*{}*", timestamp)),
+        (None, None) => {}
+    }
+    lines.join("\n")
+}
+
+/// Splits a text into at most `max_parts` parts of at most `max_len` characters each.
+///
+/// Splits happen on line boundaries where possible; only lines that are too long on their own are
+/// split in the middle. If a split happens inside a code block, the block is closed at the end of
+/// the part and reopened at the beginning of the next one. If the text doesn't fit into
+/// `max_parts` parts, the rest is dropped and a notice is appended to the last part.
+fn split_message(text: &str, max_len: usize, max_parts: usize) -> Vec<String> {
+    // Always leave room for closing a code block and for the truncation notice.
+    let limit = max_len.saturating_sub(CODE_FENCE.len() + 1 + TRUNCATION_NOTICE.chars().count());
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut part_len = 0;
+    // The line that opened the code block we're currently in, if any.
+    let mut open_fence: Option<&str> = None;
+    for line in text.split('\n') {
+        // Room that has to be left in a new part for reopening the current code block.
+        let reopen_len = open_fence.map_or(0, |f| f.chars().count() + 1);
+        let max_piece_len = std::cmp::max(limit.saturating_sub(reopen_len), 1);
+        let chars = line.chars().collect::<Vec<char>>();
+        // Empty lines still need to produce one (empty) piece.
+        let pieces = if chars.is_empty() {
+            vec![String::new()]
+        } else {
+            chars.chunks(max_piece_len).map(|c| c.iter().cloned().collect()).collect()
+        };
+        for piece in pieces {
+            let piece_len = piece.chars().count();
+            let separator_len = if part.is_empty() { 0 } else { 1 };
+            if part_len + separator_len + piece_len > limit && !part.is_empty() {
+                finish_part(&mut parts, &mut part, open_fence);
+                if let Some(fence) = open_fence {
+                    part.push_str(fence);
+                }
+                part_len = part.chars().count();
+            }
+            if !part.is_empty() {
+                part.push('\n');
+                part_len += 1;
+            }
+            part.push_str(&piece);
+            part_len += piece_len;
+        }
+        if line.trim_left().starts_with(CODE_FENCE) {
+            open_fence = if open_fence.is_some() { None } else { Some(line) };
+        }
+    }
+    finish_part(&mut parts, &mut part, None);
+    if parts.len() > max_parts {
+        parts.truncate(max_parts);
+        if let Some(last) = parts.last_mut() {
+            last.push_str(TRUNCATION_NOTICE);
+        }
+    }
+    parts
+}
+
+/// Moves the current part to the finished parts (unless it's blank), closing the code block that
+/// is open, if any.
+fn finish_part(parts: &mut Vec<String>, part: &mut String, open_fence: Option<&str>) {
+    if open_fence.is_some() {
+        part.push('\n');
+        part.push_str(CODE_FENCE);
+    }
+    if !part.trim().is_empty() {
+        parts.push(part.clone());
+    }
+    part.clear();
+}
+
+#[cfg(test)]
+mod tests_render_plain_text {
+    use super::render_plain_text;
+    use model::Reply;
+    use time;
+
+    #[test]
+    fn text_only() {
+        assert_eq!("Ok.", render_plain_text(&Reply::new("Ok.")));
+    }
+
+    #[test]
+    fn all_parts() {
+        let reply = Reply {
+            title: Some("Status".to_owned()),
+            text: "Some text.".to_owned(),
+            fields: vec![("Tier 6".to_owned(), "1".to_owned()),
+                         ("Tier 8".to_owned(), "2".to_owned())],
+            footer: Some("Footer".to_owned()),
+            timestamp: Some(time::at_utc(time::Timespec::new(1475000000, 0))),
+            colour: Some(0x00ff00),
+        };
+        assert_eq!("**Status**\nSome text.\n**Tier 6:** 1\n**Tier 8:** 2\n*Footer | 2016-09-27 \
+                    18:13 UTC*",
+                   render_plain_text(&reply));
+    }
+
+    #[test]
+    fn timestamp_without_footer() {
+        let mut reply = Reply::new("");
+        reply.timestamp = Some(time::at_utc(time::Timespec::new(0, 0)));
+        assert_eq!("*1970-01-01 00:00 UTC*", render_plain_text(&reply));
+    }
+}
+
+#[cfg(test)]
+mod tests_split_message {
+    use super::{split_message, TRUNCATION_NOTICE};
+    use std::iter;
+
+    fn line(c: &str, len: usize) -> String {
+        iter::repeat(c).take(len).collect()
+    }
+
+    #[test]
+    fn short_message_unchanged() {
+        assert_eq!(vec!["a\nßb\n\nc".to_owned()], split_message("a\nßb\n\nc", 100, 5));
+    }
+
+    #[test]
+    fn splits_on_line_boundaries() {
+        let text = vec![line("a", 30), line("ß", 30), line("c", 30)].join("\n");
+        let parts = split_message(&text, 100, 5);
+        assert_eq!(vec![line("a", 30) + "\n" + &line("ß", 30), line("c", 30)], parts);
+    }
+
+    #[test]
+    fn parts_within_limit() {
+        let text = (0..200).map(|i| line("x", i % 37)).collect::<Vec<String>>().join("\n");
+        let parts = split_message(&text, 120, 100);
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(part.chars().count() <= 120);
+        }
+        assert_eq!(text.replace("\n", ""), parts.concat().replace("\n", ""));
+    }
+
+    #[test]
+    fn splits_overlong_line() {
+        let text = line("ä", 250);
+        let parts = split_message(&text, 100, 5);
+        assert!(parts.len() > 2);
+        for part in &parts {
+            assert!(part.chars().count() <= 100);
+        }
+        assert_eq!(text, parts.concat());
+    }
+
+    #[test]
+    fn reopens_code_blocks() {
+        let mut lines = vec!["intro".to_owned(), "```rust".to_owned()];
+        for _ in 0..5 {
+            lines.push(line("c", 30));
+        }
+        lines.push("```".to_owned());
+        lines.push("outro".to_owned());
+        let parts = split_message(&lines.join("\n"), 100, 10);
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(part.chars().count() <= 100);
+            // Every part has balanced code fences.
+            assert_eq!(0, part.matches("```").count() % 2);
+        }
+        assert!(parts[1].starts_with("```rust\n"));
+        assert!(parts[0].ends_with("\n```"));
+        assert!(parts.last().unwrap().ends_with("```\noutro"));
+    }
+
+    #[test]
+    fn caps_number_of_parts() {
+        let text = (0..100).map(|_| line("x", 50)).collect::<Vec<String>>().join("\n");
+        let parts = split_message(&text, 100, 3);
+        assert_eq!(3, parts.len());
+        assert!(parts[2].ends_with(TRUNCATION_NOTICE));
+        for part in &parts {
+            assert!(part.chars().count() <= 100);
         }
     }
+
+    #[test]
+    fn skips_blank_parts() {
+        assert_eq!(Vec::<String>::new(), split_message("\n\n  \n", 100, 5));
+    }
 }
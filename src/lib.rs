@@ -0,0 +1,28 @@
+//! Everything the bot does short of connecting to Discord and running: parsing messages, keeping
+//! track of who wants to play and composing replies. It's a library so it can be fuzzed.
+
+extern crate discord;
+extern crate time;
+extern crate rustc_serialize;
+extern crate chrono;
+extern crate chrono_tz;
+#[macro_use]
+extern crate log;
+#[cfg(test)]
+extern crate rand;
+
+pub mod discord_connection;
+pub mod common;
+pub mod sh_status;
+pub mod message_parser;
+pub mod message_formatter;
+pub mod model;
+pub mod replier;
+pub mod logger;
+pub mod timezone;
+pub mod server_cache;
+pub mod vocabulary;
+pub mod commands;
+
+/// The prefix messages in public channels have to start with, unless the guild set its own.
+pub const DEFAULT_PREFIX: &'static str = ".sh";
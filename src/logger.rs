@@ -0,0 +1,249 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use log::{self, Log, Level, LevelFilter, Metadata, Record};
+use time;
+
+/// Environment variable with the maximum level of messages that are logged.
+const LEVEL_VAR: &'static str = "SH_BOT_LOG_LEVEL";
+/// Environment variable with the path of the log file. Logs go to stderr if it's not set.
+const FILE_VAR: &'static str = "SH_BOT_LOG_FILE";
+/// Environment variable with the size in bytes at which the log file is rotated.
+const MAX_SIZE_VAR: &'static str = "SH_BOT_LOG_MAX_SIZE";
+/// Environment variable with the number of rotated log files that are kept.
+const KEEP_VAR: &'static str = "SH_BOT_LOG_KEEP";
+
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_KEEP: u32 = 5;
+
+#[derive(PartialEq, Debug)]
+pub struct LogConfig {
+    pub level: LevelFilter,
+    pub output: LogOutput,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum LogOutput {
+    Stderr,
+    File {
+        path: String,
+        max_size: u64,
+        keep: u32,
+    },
+}
+
+impl LogConfig {
+    /// Reads the configuration from the environment, using defaults for anything that isn't set.
+    pub fn from_env() -> Result<Self, String> {
+        let level = match env::var(LEVEL_VAR) {
+            Ok(level_str) => try!(parse_level(&level_str)),
+            Err(_) => DEFAULT_LEVEL,
+        };
+        let output = match env::var(FILE_VAR) {
+            Ok(path) => {
+                let max_size = try!(parse_var(MAX_SIZE_VAR, DEFAULT_MAX_SIZE));
+                let keep = try!(parse_var(KEEP_VAR, DEFAULT_KEEP));
+                LogOutput::File {
+                    path: path,
+                    max_size: max_size,
+                    keep: keep,
+                }
+            }
+            Err(_) => LogOutput::Stderr,
+        };
+        Ok(LogConfig {
+            level: level,
+            output: output,
+        })
+    }
+}
+
+fn parse_level(level_str: &str) -> Result<LevelFilter, String> {
+    level_str.parse::<LevelFilter>()
+        .map_err(|_| format!("Invalid log level \"{}\".", level_str))
+}
+
+fn parse_var<T: ::std::str::FromStr>(var: &str, default: T) -> Result<T, String> {
+    match env::var(var) {
+        Ok(value_str) => {
+            value_str.parse::<T>()
+                .map_err(|_| format!("Invalid value \"{}\" for {}.", value_str, var))
+        }
+        Err(_) => Ok(default),
+    }
+}
+
+/// Installs the logger. Has to be called once at the start of the program, messages logged before
+/// are lost.
+pub fn init(config: LogConfig) -> Result<(), String> {
+    let output = match config.output {
+        LogOutput::Stderr => Output::Stderr,
+        LogOutput::File { path, max_size, keep } => {
+            Output::File(try!(RotatingFile::open(path, max_size, keep)
+                .map_err(|err| format!("Unable to open log file: {}", err))))
+        }
+    };
+    let logger = Logger {
+        level: config.level,
+        output: Mutex::new(output),
+    };
+    try!(log::set_boxed_logger(Box::new(logger)).map_err(|err| format!("{}", err)));
+    log::set_max_level(config.level);
+    Ok(())
+}
+
+struct Logger {
+    level: LevelFilter,
+    output: Mutex<Output>,
+}
+
+enum Output {
+    Stderr,
+    File(RotatingFile),
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_record(&time::now_utc(), record.level(), record.target(), record.args());
+        // If the lock is poisoned, some other thread panicked while logging. Nothing we can do
+        // about that, but the output itself is still usable.
+        let mut output = match self.output.lock() {
+            Ok(output) => output,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        // There's nowhere to report errors while logging, so they're ignored.
+        let _ = match *output {
+            Output::Stderr => io::stderr().write_all(line.as_bytes()),
+            Output::File(ref mut file) => file.write_line(&line),
+        };
+    }
+
+    fn flush(&self) {
+        if let Ok(mut output) = self.output.lock() {
+            let _ = match *output {
+                Output::Stderr => io::stderr().flush(),
+                Output::File(ref mut file) => file.file.flush(),
+            };
+        }
+    }
+}
+
+fn format_record(now: &time::Tm,
+                 level: Level,
+                 target: &str,
+                 args: &::std::fmt::Arguments)
+                 -> String {
+    format!("{} {:<5} [{}] {}\n", now.rfc3339(), level, target, args)
+}
+
+/// A log file that is rotated once it reaches a maximum size. The current file is at the path,
+/// older ones at path.1, path.2 etc., up to the number of files that are kept.
+struct RotatingFile {
+    path: String,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: u32,
+}
+
+impl RotatingFile {
+    fn open(path: String, max_size: u64, keep: u32) -> io::Result<Self> {
+        let file = try!(OpenOptions::new().create(true).append(true).open(&path));
+        let size = try!(file.metadata()).len();
+        Ok(RotatingFile {
+            path: path,
+            file: file,
+            size: size,
+            max_size: max_size,
+            keep: keep,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            try!(self.rotate());
+        }
+        try!(self.file.write_all(line.as_bytes()));
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            try!(fs::remove_file(&self.path));
+        } else {
+            // The oldest file is overwritten by the rename.
+            for i in (1..self.keep).rev() {
+                let from = format!("{}.{}", self.path, i);
+                if fs::metadata(&from).is_ok() {
+                    try!(fs::rename(&from, format!("{}.{}", self.path, i + 1)));
+                }
+            }
+            try!(fs::rename(&self.path, format!("{}.1", self.path)));
+        }
+        self.file = try!(OpenOptions::new().create(true).append(true).open(&self.path));
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_logger {
+    use super::{parse_level, format_record, RotatingFile};
+    use log::{Level, LevelFilter};
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use time;
+
+    #[test]
+    fn levels() {
+        assert_eq!(Ok(LevelFilter::Debug), parse_level("debug"));
+        assert_eq!(Ok(LevelFilter::Warn), parse_level("WARN"));
+        assert_eq!(Ok(LevelFilter::Off), parse_level("off"));
+        assert!(parse_level("loud").is_err());
+    }
+
+    #[test]
+    fn format() {
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        assert_eq!("2016-09-27T18:13:20Z INFO  [discord_sh_bot] user 1 channel 2: Status\n",
+                   format_record(&now,
+                                 Level::Info,
+                                 "discord_sh_bot",
+                                 &format_args!("user {} channel {}: {}", 1, 2, "Status")));
+    }
+
+    fn read(path: &str) -> String {
+        let mut s = String::new();
+        File::open(path).unwrap().read_to_string(&mut s).unwrap();
+        s
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = env::temp_dir().join(format!("sh_bot_log_test_{}", time::precise_time_ns()));
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("bot.log").to_str().unwrap().to_owned();
+        {
+            let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+            for line in &["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+                file.write_line(line).unwrap();
+            }
+        }
+        assert_eq!("dddddd\n", read(&path));
+        assert_eq!("cccccc\n", read(&format!("{}.1", path)));
+        assert_eq!("bbbbbb\n", read(&format!("{}.2", path)));
+        assert!(fs::metadata(format!("{}.3", path)).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
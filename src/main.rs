@@ -1,75 +1,133 @@
+extern crate discord_sh_bot;
 extern crate discord;
 extern crate time;
 extern crate rustc_serialize;
+extern crate ctrlc;
+extern crate chrono_tz;
+#[macro_use]
+extern crate log;
 
-mod discord_connection;
-mod common;
-mod sh_status;
-mod message_parser;
-mod model;
-mod replier;
-
-use std::collections::HashSet;
 use std::sync::mpsc;
-use discord::model::{Event, Channel, CurrentUser, Message};
-use discord_connection::{DiscordConnection, BotConnection};
-use model::{Want, Request, Timeframe};
-use sh_status::ShStatus;
+use std::time::Duration;
+use std::fs::File;
+use std::io::{Read, Write};
+use rustc_serialize::json;
+use discord::model::{Event, Channel, CurrentUser, Message, ServerId};
+use chrono_tz::Tz;
+use discord_sh_bot::{commands, logger, message_parser, replier, DEFAULT_PREFIX};
+use discord_sh_bot::discord_connection::{DiscordConnection, BotConnection};
+use discord_sh_bot::model::{Request, Reply, Command};
+use discord_sh_bot::sh_status::ShStatus;
+use discord_sh_bot::server_cache::ServerCache;
+use discord_sh_bot::vocabulary::{Vocabulary, Language};
+use discord_sh_bot::commands::{CommandSpec, Permission};
 
-const BOT_COMMAND: &'static str = ".sh";
+/// Environment variable with the path of the file the state is saved to on shutdown.
+const STATE_FILE_VAR: &'static str = "SH_BOT_STATE_FILE";
+const DEFAULT_STATE_FILE: &'static str = "sh_status.json";
+/// How long to wait for an event before checking whether to shut down.
+const SHUTDOWN_CHECK_INTERVAL_MS: u64 = 250;
 
 fn main() {
+    if let Err(msg) = logger::LogConfig::from_env().and_then(logger::init) {
+        eprintln!("Unable to set up logging: {}", msg);
+        std::process::exit(1);
+    }
     let token: String;
     if let Some(t) = std::env::args().nth(1) {
         token = t;
     } else {
-        // TODO log, don't print
-        println!("Pass the bot token as an argument.");
+        error!("Pass the bot token as an argument.");
         std::process::exit(1);
     }
 
+    let state_file = std::env::var(STATE_FILE_VAR).unwrap_or(DEFAULT_STATE_FILE.to_owned());
+
     let (sender, receiver) = mpsc::channel();
+    if let Err(msg) = listen_for_signals(sender.clone()) {
+        warn!("Unable to handle signals, only stdin can be used to shut down: {}", msg);
+    }
     std::thread::spawn(move || listen_for_shutdown(sender));
-    ShBot::new(&token, receiver).run();
+    ShBot::new(&token, state_file, receiver).run();
 }
 
 fn listen_for_shutdown(shutdown_sender: mpsc::Sender<()>) {
-    println!("Enter \"s\" or \"shutdown\" to shut down gracefully.");
+    info!("Enter \"s\" or \"shutdown\" to shut down gracefully.");
     let mut buf = String::new();
     let stdin = std::io::stdin();
     loop {
-        stdin.read_line(&mut buf)
-            .unwrap_or_else(|err| {
-                println!("Unable to read from stdin: {}", err);
-                0
-            });
+        buf.clear();
+        match stdin.read_line(&mut buf) {
+            Ok(0) => {
+                // EOF, e.g. when running as a service. Nothing more will come, so stop listening
+                // and leave shutting down to the signal handler.
+                info!("Stdin is closed, send SIGTERM or SIGINT to shut down gracefully.");
+                return;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                warn!("Unable to read from stdin, send SIGTERM or SIGINT to shut down gracefully: \
+                       {}",
+                      err);
+                return;
+            }
+        }
         let input = buf.trim();
         // TODO unhardcode shutdown commands
         if input == "s" || input == "shutdown" {
             break;
         }
     }
-    shutdown_sender.send(()).expect("Unable to send shutdown to main loop.");
-    println!("Sent the shutdown signal to the main thread. It will exit as soon as it receives \
-              the next event. (Don't ask...)");
+    send_shutdown(&shutdown_sender);
+}
+
+/// Sends the shutdown signal to the main thread on SIGTERM or SIGINT. If a second signal arrives
+/// before the main thread is done, the process exits immediately.
+fn listen_for_signals(shutdown_sender: mpsc::Sender<()>) -> Result<(), String> {
+    let mut received = false;
+    ctrlc::set_handler(move || {
+            if received {
+                warn!("Received another signal, exiting immediately.");
+                std::process::exit(1);
+            }
+            received = true;
+            send_shutdown(&shutdown_sender);
+        })
+        .map_err(|err| format!("{}", err))
+}
+
+fn send_shutdown(shutdown_sender: &mpsc::Sender<()>) {
+    if shutdown_sender.send(()).is_err() {
+        // The main thread has already stopped listening, i.e. it's shutting down anyway.
+        return;
+    }
+    info!("Sent the shutdown signal to the main thread.");
 }
 
 struct ShBot<D: DiscordConnection> {
     discord: D,
     me: CurrentUser,
     shutdown_receiver: mpsc::Receiver<()>,
+    state_file: String,
     sh_status: ShStatus,
+    server_cache: ServerCache,
 }
 
 // TODO do i have to specify which kind of discordconnection?
 impl ShBot<BotConnection> {
-    fn new(token: &str, shutdown_receiver: mpsc::Receiver<()>) -> Self {
+    fn new(token: &str, state_file: String, shutdown_receiver: mpsc::Receiver<()>) -> Self {
+        let sh_status = load_state(&state_file).unwrap_or_else(|msg| {
+            warn!("Starting with empty state: {}", msg);
+            ShStatus::new()
+        });
         let (d, me) = BotConnection::from_bot_token(token);
         ShBot {
             discord: d,
             me: me,
             shutdown_receiver: shutdown_receiver,
-            sh_status: ShStatus::new(),
+            state_file: state_file,
+            sh_status: sh_status,
+            server_cache: ServerCache::new(),
         }
     }
 
@@ -77,34 +135,49 @@ impl ShBot<BotConnection> {
         while let Err(mpsc::TryRecvError::Empty) = self.shutdown_receiver.try_recv() {
             self.handle_event();
         }
+        match save_state(&self.state_file, &self.sh_status) {
+            Ok(()) => info!("Saved state to {}.", self.state_file),
+            Err(msg) => error!("Error saving state: {}", msg),
+        }
         self.discord.shutdown();
     }
 
     fn handle_event(&mut self) {
-        match self.discord.recv_event() {
+        let timeout = Duration::from_millis(SHUTDOWN_CHECK_INTERVAL_MS);
+        let event = match self.discord.recv_event(timeout) {
+            Ok(Some(event)) => event,
+            Ok(None) => return,
             Err(msg) => {
-                // TODO log, don't print
-                println!("Error receiving event: {}", msg);
+                error!("Error receiving event: {}", msg);
+                return;
             }
-            Ok(Event::MessageCreate(mut msg)) => {
+        };
+        self.server_cache.update(&event);
+        match event {
+            Event::MessageCreate(msg) => {
+                let (msg_channel_id, msg_author_id) = (msg.channel_id.0, msg.author.id.0);
                 match self.message_concerns_me(msg) {
-                    Ok((false, _)) => {
+                    Ok(None) => {
                         // Message not directed at the bot.
-                        return;
                     }
-                    Ok((true, new_msg)) => msg = new_msg,
-                    Err(msg) => {
-                        // TODO log, don't print
-                        println!("Error getting channel information: {}", msg);
-                        return;
+                    Ok(Some((msg, server_id))) => self.handle_message(msg, server_id),
+                    Err(err) => {
+                        error!("Error getting information about channel {} (message from user \
+                                {}): {}",
+                               msg_channel_id,
+                               msg_author_id,
+                               err);
                     }
                 }
-                self.handle_message(msg);
             }
-            Ok(Event::PresenceUpdate { presence, server_id: _, roles: _ }) => {
+            Event::PresenceUpdate { presence, server_id: _, roles: _ } => {
+                debug!("User {} is now {:?}.", presence.user_id.0, presence.status);
                 self.sh_status.set_user_changed_status(presence.user_id, presence.status);
+                if let Some(ref user) = presence.user {
+                    self.sh_status.set_user_name(user.id, &user.name);
+                }
             }
-            Ok(Event::PresencesReplace(presences)) => {
+            Event::PresencesReplace(presences) => {
                 // I _think_ that PresencesReplace is a bulk presence update.
                 // TODO but it's not documented
                 for presence in presences {
@@ -117,92 +190,212 @@ impl ShBot<BotConnection> {
         }
     }
 
-    fn message_concerns_me(&self, mut msg: Message) -> Result<(bool, Message), String> {
+    /// Returns the message with the prefix or mention removed, and the server it was sent in (None
+    /// for private channels), if it's addressed at the bot.
+    fn message_concerns_me(&self, mut msg: Message)
+                           -> Result<Option<(Message, Option<ServerId>)>, String> {
         if msg.author.id == self.me.id {
             // Don't respond to own messages.
-            return Ok((false, msg));
+            return Ok(None);
         }
         // Get info about the channel the message arrived at.
         // TODO cache
-        match self.discord.get_channel(msg.channel_id) {
-            Ok(channel) => {
-                if let Channel::Public(_) = channel {
-                    // Public channel, only handle if it was addressed at the bot (i.e.
-                    // prefixed with the bot command).
-                    let (first, second) = common::str_head_tail(&msg.content);
-                    if first != BOT_COMMAND {
-                        // Command doesn't start with bot command, ignore.
-                        return Ok((false, msg));
+        match try!(self.discord.get_channel(msg.channel_id)) {
+            Channel::Public(channel) => {
+                // Public channel, only handle if it was addressed at the bot (i.e. prefixed with
+                // the guild's prefix or a mention of the bot).
+                let prefix = self.sh_status
+                    .get_guild_prefix(channel.server_id)
+                    .unwrap_or(DEFAULT_PREFIX);
+                match message_parser::strip_trigger(&msg.content, prefix, self.me.id) {
+                    Some(content) => {
+                        // Handle message, but without the prefix or mention.
+                        msg.content = content;
+                        Ok(Some((msg, Some(channel.server_id))))
                     }
-                    // Handle message, but remove bot command from the beginning.
-                    msg.content = second;
-                    Ok((true, msg))
-                } else {
-                    // Private channel, handle.
-                    Ok((true, msg))
+                    None => Ok(None),
                 }
             }
-            Err(msg) => return Err(msg),
+            _ => {
+                // Private channel, handle. Addressing the bot isn't necessary, but allowed.
+                if let Some(content) =
+                    message_parser::strip_trigger(&msg.content, DEFAULT_PREFIX, self.me.id) {
+                    msg.content = content;
+                }
+                Ok(Some((msg, None)))
+            }
         }
     }
 
-    fn handle_message(&mut self, msg: Message) {
-        let req = message_parser::parse_message(&msg);
-        match req {
-            Request::None => {}
-            Request::Unknown => self.handle_unknown(msg),
-            Request::Help => self.handle_help(msg),
-            Request::Want { time, wants } => self.handle_want(msg, time, wants),
-            Request::DontWant => self.handle_dont_want(msg),
-            Request::Status => self.handle_status(msg),
+    fn handle_message(&mut self, msg: Message, server_id: Option<ServerId>) {
+        let tz = self.sh_status.get_user_timezone(msg.author.id).unwrap_or(Tz::UTC);
+        let vocabulary = self.vocabulary(server_id);
+        let now = time::now_utc();
+        let parsed = message_parser::parse_command(&msg.content, now, tz, &vocabulary);
+        info!("User {} in channel {}: {:?}",
+              msg.author.id.0,
+              msg.channel_id.0,
+              parsed.request);
+        match (parsed.spec, parsed.request) {
+            (_, Request::None) => {}
+            (_, Request::Unknown) => {
+                let suggestion = self.suggest(&msg, server_id, tz, &vocabulary);
+                self.handle_unknown(msg, suggestion, &vocabulary)
+            }
+            (_, Request::Invalid { command, reason, position }) => {
+                let suggestion = self.suggest(&msg, server_id, tz, &vocabulary);
+                self.handle_invalid(msg, command, &reason, position, suggestion, &vocabulary)
+            }
+            (Some(spec), request) => {
+                let has_arguments = parsed.has_arguments;
+                self.handle_command(msg, server_id, spec, has_arguments, request, now, &vocabulary)
+            }
+            (None, request) => error!("Request {:?} doesn't belong to a command.", request),
         }
     }
 
-    fn handle_unknown(&self, msg: Message) {
-        let reply = replier::unknown_request(&msg.content);
-        if let Err(msg) = self.discord
-            .send_message(&msg.channel_id, &reply, false) {
-            // TODO log, don't print
-            println!("Failed to send message: {}", msg);
+    /// Returns the keywords understood in the server, or all of them in private channels.
+    fn vocabulary(&self, server_id: Option<ServerId>) -> Vocabulary {
+        match server_id {
+            Some(server_id) => {
+                Vocabulary::new(self.sh_status
+                    .get_guild_language(server_id)
+                    .unwrap_or(Language::English))
+            }
+            None => Vocabulary::all(),
         }
     }
 
-    fn handle_help(&self, msg: Message) {
-        let reply = replier::help();
-        if let Err(msg) = self.discord
-            .send_message(&msg.channel_id, &reply, false) {
-            // TODO log, don't print
-            println!("Failed to send message: {}", msg);
-        }
+    /// Returns a corrected version of a mistyped message, as the user would have to type it (i.e.
+    /// with the guild's prefix), if there is one.
+    fn suggest(&self,
+               msg: &Message,
+               server_id: Option<ServerId>,
+               tz: Tz,
+               vocabulary: &Vocabulary)
+               -> Option<String> {
+        message_parser::suggest(&msg.content, time::now_utc(), tz, vocabulary).map(|suggestion| {
+            match server_id {
+                Some(server_id) => {
+                    let prefix = self.sh_status
+                        .get_guild_prefix(server_id)
+                        .unwrap_or(DEFAULT_PREFIX);
+                    format!("{} {}", prefix, suggestion)
+                }
+                None => suggestion,
+            }
+        })
     }
 
-    fn handle_want(&mut self, msg: Message, time: Timeframe, wants: HashSet<Want>) {
-        let ud = self.sh_status.set_user_wants_sh(msg.author.id, time, wants);
-        let reply = replier::want(ud);
-        if let Err(msg) = self.discord
-            .send_message(&msg.channel_id, &reply, false) {
-            // TODO log, don't print
-            println!("Failed to send message: {}", msg);
-        }
+    fn handle_unknown(&self, msg: Message, suggestion: Option<String>, vocabulary: &Vocabulary) {
+        let reply = replier::unknown_request(&msg.content,
+                                             suggestion.as_ref().map(|s| &**s),
+                                             vocabulary);
+        self.send_reply(&msg, &reply);
     }
 
-    fn handle_dont_want(&mut self, msg: Message) {
-        self.sh_status.set_user_doesnt_want_sh(msg.author.id);
-        let reply = replier::dont_want();
-        if let Err(msg) = self.discord
-            .send_message(&msg.channel_id, &reply, false) {
-            // TODO log, don't print
-            println!("Failed to send message: {}", msg);
+    fn handle_invalid(&self,
+                      msg: Message,
+                      command: Command,
+                      reason: &str,
+                      position: usize,
+                      suggestion: Option<String>,
+                      vocabulary: &Vocabulary) {
+        let reply = replier::invalid_request(&msg.content,
+                                             command,
+                                             reason,
+                                             position,
+                                             suggestion.as_ref().map(|s| &**s),
+                                             vocabulary);
+        self.send_reply(&msg, &reply);
+    }
+
+    /// Checks whether the author of the message may use the command as requested, and if so,
+    /// carries out the request.
+    fn handle_command(&mut self,
+                      msg: Message,
+                      server_id: Option<ServerId>,
+                      spec: &CommandSpec,
+                      has_arguments: bool,
+                      request: Request,
+                      now: time::Tm,
+                      vocabulary: &Vocabulary) {
+        if let (Some(server_id), Permission::ManageServerForArguments, true) =
+            (server_id, spec.permission, has_arguments) {
+            match self.may_manage(&msg, server_id) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.send_reply(&msg, &replier::setting_not_allowed(spec.command, vocabulary));
+                    return;
+                }
+                Err(()) => {
+                    let reply = replier::setting_permissions_unknown(spec.command, vocabulary);
+                    self.send_reply(&msg, &reply);
+                    return;
+                }
+            }
+        }
+        let reply = {
+            let mut ctx = commands::Context {
+                sh_status: &mut self.sh_status,
+                user_id: msg.author.id,
+                server_id: server_id,
+                vocabulary: vocabulary,
+                now: now,
+            };
+            (spec.handle)(request, &mut ctx)
+        };
+        // Now that the user may have data, it can be shown by name.
+        self.sh_status.set_user_name(msg.author.id, &msg.author.name);
+        if let Some(reply) = reply {
+            self.send_reply(&msg, &reply);
         }
     }
 
-    fn handle_status(&mut self, msg: Message) {
-        let status_report = self.sh_status.get_current_status();
-        let reply = replier::status(&status_report);
-        if let Err(msg) = self.discord
-            .send_message(&msg.channel_id, &reply, false) {
-            // TODO log, don't print
-            println!("Failed to send message: {}", msg);
+    /// Checks whether the author of the message may change the server's settings. Errors are
+    /// logged.
+    fn may_manage(&self, msg: &Message, server_id: ServerId) -> Result<bool, ()> {
+        self.discord
+            .get_member(server_id, msg.author.id)
+            .and_then(|member| {
+                self.server_cache
+                    .may_manage(server_id, msg.author.id, &member.roles)
+                    .ok_or_else(|| format!("Server {} is unknown.", server_id.0))
+            })
+            .map_err(|err| {
+                error!("Unable to check whether user {} may manage server {}: {}",
+                       msg.author.id.0,
+                       server_id.0,
+                       err);
+            })
+    }
+
+    /// Sends the reply to the channel the message came from.
+    fn send_reply(&self, msg: &Message, reply: &Reply) {
+        if let Err(err) = self.discord.send_reply(&msg.channel_id, reply) {
+            error!("Failed to send reply to user {} in channel {}: {}",
+                   msg.author.id.0,
+                   msg.channel_id.0,
+                   err);
         }
     }
 }
+
+fn load_state(path: &str) -> Result<ShStatus, String> {
+    let mut encoded = String::new();
+    try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut encoded))
+        .map_err(|err| format!("Unable to read state file {}: {}", path, err)));
+    json::decode(&encoded).map_err(|err| format!("Unable to decode state file {}: {}", path, err))
+}
+
+/// Writes the state to a temporary file first, so an existing state file is never left half
+/// written.
+fn save_state(path: &str, sh_status: &ShStatus) -> Result<(), String> {
+    let encoded = try!(json::encode(sh_status).map_err(|err| format!("{}", err)));
+    let tmp_path = format!("{}.tmp", path);
+    File::create(&tmp_path)
+        .and_then(|mut f| f.write_all(encoded.as_bytes()))
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|err| format!("Unable to write state file {}: {}", path, err))
+}
@@ -0,0 +1,417 @@
+use std::collections::HashSet;
+use time;
+use time::Duration;
+use chrono_tz::Tz;
+use commands;
+use model::{Command, Request, Tier, Timeframe, TimeframeKind, Want, TIERS, WEEKDAYS};
+use timezone;
+use vocabulary::{Vocabulary, Keyword};
+
+/// Formats a request as the command that asks for it, in one canonical way: parsing the command
+/// at the same time in the same timezone returns the same request. Keywords are taken from the
+/// vocabulary, times are relative to `now` and in the timezone `tz`. Returns None for requests
+/// that don't stand for a command (`None`, `Unknown` and `Invalid`).
+pub fn format_request(request: &Request,
+                      now: &time::Tm,
+                      tz: Tz,
+                      vocabulary: &Vocabulary)
+                      -> Option<String> {
+    let (command, arguments) = match *request {
+        Request::None |
+        Request::Unknown |
+        Request::Invalid { .. } => return None,
+        Request::Help(None) => (Command::Help, String::new()),
+        Request::Help(Some(command)) => (Command::Help, commands::spec(command).name(vocabulary)),
+        Request::Want { ref time, ref wants } => {
+            (Command::Want, format_want(time, wants, now, tz))
+        }
+        Request::DontWant { ref tiers, ref kinds } => {
+            (Command::DontWant, format_dont_want(tiers, kinds))
+        }
+        Request::Status => (Command::Status, String::new()),
+        Request::ShowMe => (Command::Me, String::new()),
+        Request::ShowTimezone => (Command::Timezone, String::new()),
+        Request::SetTimezone(tz) => (Command::Timezone, tz.name().to_owned()),
+        Request::ListSchedules => (Command::Schedules, String::new()),
+        Request::RemoveSchedule(number) => {
+            (Command::Schedules, format!("{} {}", vocabulary.word(Keyword::Remove), number))
+        }
+        Request::ShowPrefix => (Command::Prefix, String::new()),
+        Request::SetPrefix(ref prefix) => (Command::Prefix, escape(prefix)),
+        Request::ShowLanguage => (Command::Language, String::new()),
+        Request::SetLanguage(language) => (Command::Language, language.name().to_owned()),
+    };
+    let name = commands::spec(command).name(vocabulary);
+    if arguments.is_empty() {
+        Some(name)
+    } else {
+        Some(format!("{} {}", name, arguments))
+    }
+}
+
+/// Escapes double quotes and backslashes with a backslash, so the text is read as it is.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Formats the arguments of a want, e.g. "8 10 for 1:30h". The tiers are left out if all of them
+/// are wanted, the timeframe if it's until logout.
+fn format_want(time: &Timeframe, wants: &HashSet<Want>, now: &time::Tm, tz: Tz) -> String {
+    let mut parts = Vec::new();
+    if wants.len() < TIERS.len() {
+        let tiers = wants.iter().map(|want| want.tier).collect();
+        parts.extend(format_tiers(&tiers));
+    }
+    let timeframe = format_timeframe(time, now, tz);
+    if !timeframe.is_empty() {
+        parts.push(timeframe);
+    }
+    parts.join(" ")
+}
+
+/// Formats the arguments of a dont want, e.g. "6 until every".
+fn format_dont_want(tiers: &HashSet<Tier>, kinds: &HashSet<TimeframeKind>) -> String {
+    let mut parts = format_tiers(tiers);
+    if kinds.contains(&TimeframeKind::Always) {
+        parts.push("always".to_owned());
+    }
+    // "until" stands for both kinds of timed wants, "for" only for timespans.
+    if kinds.contains(&TimeframeKind::UntilLogout) {
+        parts.push("until".to_owned());
+    } else if kinds.contains(&TimeframeKind::Timespan) {
+        parts.push("for".to_owned());
+    }
+    if kinds.contains(&TimeframeKind::Window) {
+        parts.push("from".to_owned());
+    }
+    if kinds.contains(&TimeframeKind::Weekly) {
+        parts.push("every".to_owned());
+    }
+    parts.join(" ")
+}
+
+/// Formats the tiers in ascending order.
+fn format_tiers(tiers: &HashSet<Tier>) -> Vec<String> {
+    TIERS.iter()
+        .filter(|tier| tiers.contains(tier))
+        .map(|tier| tier.number().to_string())
+        .collect()
+}
+
+/// Formats a timeframe the way it's given in a want, e.g. "until 22:30" or "every mon-fri
+/// 19:00-22:00". Until logout is the default and formatted as nothing.
+pub fn format_timeframe(time: &Timeframe, now: &time::Tm, tz: Tz) -> String {
+    match *time {
+        Timeframe::Always => "always".to_owned(),
+        Timeframe::UntilLogout => String::new(),
+        Timeframe::Timespan { until } => {
+            match format_until(&until, now, tz) {
+                Some(until) => format!("until {}", until),
+                None => format!("for {}", format_duration(until - *now)),
+            }
+        }
+        Timeframe::Window { from, until } => {
+            // A window that started today is the one meant without "tomorrow", as it can't have
+            // ended yet.
+            let today = timezone::local_date(now, tz);
+            let tomorrow = if today.is_some() && timezone::local_date(&from, tz) == today {
+                ""
+            } else {
+                "tomorrow "
+            };
+            format!("from {}{} to {}",
+                    tomorrow,
+                    timezone::format(&from, tz, "%H:%M"),
+                    timezone::format(&until, tz, "%H:%M"))
+        }
+        Timeframe::Weekly { days, from, until } => {
+            format!("every {} {:02}:{:02}-{:02}:{:02}",
+                    format_days(days),
+                    from / 60,
+                    from % 60,
+                    until / 60,
+                    until % 60)
+        }
+    }
+}
+
+/// Returns what to write after "until" to end at the point in time, e.g. "tomorrow 01:00". Returns
+/// None if that's not possible, because it's not on a full minute, not today or tomorrow, or
+/// its time of day occurs twice and it's the second one.
+fn format_until(until: &time::Tm, now: &time::Tm, tz: Tz) -> Option<String> {
+    let timespec = until.to_timespec();
+    if timespec.sec % 60 != 0 || timespec.nsec != 0 || until <= now {
+        return None;
+    }
+    let (date, today, minutes) = match (timezone::local_date(until, tz),
+                                        timezone::local_date(now, tz),
+                                        timezone::local_weekday_and_minutes(until, tz)) {
+        (Some(date), Some(today), Some((_, minutes))) => (date, today, minutes),
+        _ => return None,
+    };
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    let on_date = timezone::from_local(date, hours, minutes, tz);
+    if on_date.map(|tm| tm.to_timespec()) != Some(timespec) {
+        return None;
+    }
+    let time_of_day = format!("{:02}:{:02}", hours, minutes);
+    // Without "tomorrow", a time that has already passed today is taken to mean tomorrow.
+    let passed_today = match timezone::from_local(today, hours, minutes, tz) {
+        Some(tm) => tm <= *now,
+        None => true,
+    };
+    let is_tomorrow = today.succ_opt() == Some(date);
+    if date == today || (is_tomorrow && passed_today) {
+        Some(time_of_day)
+    } else if is_tomorrow {
+        Some(format!("tomorrow {}", time_of_day))
+    } else {
+        None
+    }
+}
+
+/// Formats a duration in hours and minutes, e.g. "1:30h". Durations that aren't whole minutes are
+/// formatted with decimal minutes, e.g. "1h0.6m".
+fn format_duration(duration: Duration) -> String {
+    let secs = ::std::cmp::max(duration.num_seconds(), 0);
+    let (hours, minutes, secs) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if secs == 0 {
+        return format!("{}:{:02}h", hours, minutes);
+    }
+    // Parsing rounds down to whole seconds, so the decimals are rounded up. One decimal place is
+    // exact for multiples of 6 seconds, two are always close enough.
+    let (places, decimals) = if secs % 6 == 0 {
+        (1, secs / 6)
+    } else {
+        (2, (secs * 100 + 59) / 60)
+    };
+    let hours = if hours > 0 {
+        format!("{}h", hours)
+    } else {
+        String::new()
+    };
+    format!("{}{}.{:03$}m", hours, minutes, decimals, places)
+}
+
+/// Formats days of the week, bit 0 being Monday, e.g. "mon,wed,fri" or "weekends".
+fn format_days(days: u8) -> String {
+    match days {
+        0b1111111 => "day".to_owned(),
+        0b0011111 => "weekdays".to_owned(),
+        0b1100000 => "weekends".to_owned(),
+        _ => {
+            WEEKDAYS.iter()
+                .enumerate()
+                .filter(|&(i, _)| days & (1 << i) != 0)
+                .map(|(_, name)| name.to_lowercase())
+                .collect::<Vec<String>>()
+                .join(",")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_format_request {
+    use super::format_request;
+    use message_parser::parse_message;
+    use model::{Command, Request, Tier, Timeframe, TimeframeKind, Want};
+    use vocabulary::{Vocabulary, Language};
+    use chrono_tz::Tz;
+    use time;
+
+    /// 2016-09-27 18:13:20 UTC
+    fn now() -> time::Tm {
+        time::at_utc(time::Timespec::new(1475000000, 0))
+    }
+
+    fn en() -> Vocabulary {
+        Vocabulary::new(Language::English)
+    }
+
+    fn want(tiers: &[Tier], time: Timeframe) -> Request {
+        Request::Want {
+            time: time,
+            wants: tiers.iter().map(|&tier| Want { tier: tier }).collect(),
+        }
+    }
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    /// Asserts that the request is formatted as expected, and that the result parses back into
+    /// the request.
+    fn assert_formats(expected: &str, request: Request, tz: Tz, vocabulary: &Vocabulary) {
+        let formatted = format_request(&request, &now(), tz, vocabulary);
+        assert_eq!(Some(expected.to_owned()), formatted);
+        assert_eq!(request, parse_message(expected, now(), tz, vocabulary));
+    }
+
+    #[test]
+    fn want_tiers() {
+        let all = [Tier::Tier6, Tier::Tier8, Tier::Tier10];
+        assert_formats("want", want(&all, Timeframe::UntilLogout), Tz::UTC, &en());
+        assert_formats("want 8 10", want(&all[1..], Timeframe::UntilLogout), Tz::UTC, &en());
+        assert_formats("want 6 always", want(&all[..1], Timeframe::Always), Tz::UTC, &en());
+    }
+
+    #[test]
+    fn want_timespan() {
+        let all = [Tier::Tier6, Tier::Tier8, Tier::Tier10];
+        let timespan = |sec| Timeframe::Timespan { until: at(sec) };
+        assert_formats("want 8 for 1:30h",
+                       want(&all[1..2], timespan(1475000000 + 90 * 60)),
+                       Tz::UTC,
+                       &en());
+        assert_formats("want for 0:05h", want(&all, timespan(1475000300)), Tz::UTC, &en());
+        assert_formats("want for 1h0.6m", want(&all, timespan(1475003636)), Tz::UTC, &en());
+        assert_formats("want for 0.02m", want(&all, timespan(1475000001)), Tz::UTC, &en());
+        // 2016-09-27 22:30 UTC
+        assert_formats("want until 22:30", want(&all, timespan(1475015400)), Tz::UTC, &en());
+        // 2016-09-28 01:00 UTC, but 01:00 has passed today.
+        assert_formats("want until 01:00", want(&all, timespan(1475024400)), Tz::UTC, &en());
+        // 2016-09-28 22:30 UTC, 22:30 hasn't passed today.
+        assert_formats("want until tomorrow 22:30",
+                       want(&all, timespan(1475101800)),
+                       Tz::UTC,
+                       &en());
+        // 2016-09-28 00:30 in Berlin.
+        assert_formats("want until 00:30",
+                       want(&all, timespan(1475015400)),
+                       Tz::Europe__Berlin,
+                       &en());
+        // Two days ahead.
+        assert_formats("want for 52:16h",
+                       want(&all, timespan(1475000000 + 52 * 3600 + 16 * 60)),
+                       Tz::UTC,
+                       &en());
+    }
+
+    #[test]
+    fn want_window() {
+        let all = [Tier::Tier6, Tier::Tier8, Tier::Tier10];
+        let window = |from, until| {
+            Timeframe::Window {
+                from: at(from),
+                until: at(until),
+            }
+        };
+        // 2016-09-27 20:00 to 23:00 UTC
+        assert_formats("want from 20:00 to 23:00",
+                       want(&all, window(1475006400, 1475017200)),
+                       Tz::UTC,
+                       &en());
+        // 2016-09-28 10:00 to 12:00 UTC
+        assert_formats("want 10 from tomorrow 10:00 to 12:00",
+                       want(&all[2..], window(1475056800, 1475064000)),
+                       Tz::UTC,
+                       &en());
+    }
+
+    #[test]
+    fn want_weekly() {
+        let all = [Tier::Tier6, Tier::Tier8, Tier::Tier10];
+        let weekly = |days, from, until| {
+            Timeframe::Weekly {
+                days: days,
+                from: from,
+                until: until,
+            }
+        };
+        assert_formats("want every mon,wed,fri 19:00-22:30",
+                       want(&all, weekly(0b0010101, 19 * 60, 22 * 60 + 30)),
+                       Tz::UTC,
+                       &en());
+        assert_formats("want every weekdays 23:00-01:00",
+                       want(&all, weekly(0b0011111, 23 * 60, 60)),
+                       Tz::UTC,
+                       &en());
+        assert_formats("want every day 09:05-10:00",
+                       want(&all, weekly(0b1111111, 9 * 60 + 5, 10 * 60)),
+                       Tz::UTC,
+                       &en());
+    }
+
+    #[test]
+    fn dont_want() {
+        let dont_want = |tiers: &[Tier], kinds: &[TimeframeKind]| {
+            Request::DontWant {
+                tiers: tiers.iter().cloned().collect(),
+                kinds: kinds.iter().cloned().collect(),
+            }
+        };
+        assert_formats("dont want", dont_want(&[], &[]), Tz::UTC, &en());
+        assert_formats("dont want 6 8 10",
+                       dont_want(&[Tier::Tier10, Tier::Tier6, Tier::Tier8], &[]),
+                       Tz::UTC,
+                       &en());
+        assert_formats("dont want 10 always until every",
+                       dont_want(&[Tier::Tier10],
+                                 &[TimeframeKind::Weekly,
+                                   TimeframeKind::Always,
+                                   TimeframeKind::Timespan,
+                                   TimeframeKind::UntilLogout]),
+                       Tz::UTC,
+                       &en());
+        assert_formats("dont want for from",
+                       dont_want(&[], &[TimeframeKind::Timespan, TimeframeKind::Window]),
+                       Tz::UTC,
+                       &en());
+    }
+
+    #[test]
+    fn other_commands() {
+        assert_formats("help", Request::Help(None), Tz::UTC, &en());
+        assert_formats("help dont want",
+                       Request::Help(Some(Command::DontWant)),
+                       Tz::UTC,
+                       &en());
+        assert_formats("status", Request::Status, Tz::UTC, &en());
+        assert_formats("me", Request::ShowMe, Tz::UTC, &en());
+        assert_formats("timezone", Request::ShowTimezone, Tz::UTC, &en());
+        assert_formats("timezone Europe/Berlin",
+                       Request::SetTimezone(Tz::Europe__Berlin),
+                       Tz::UTC,
+                       &en());
+        assert_formats("schedules", Request::ListSchedules, Tz::UTC, &en());
+        assert_formats("schedules remove 2", Request::RemoveSchedule(2), Tz::UTC, &en());
+        assert_formats("prefix", Request::ShowPrefix, Tz::UTC, &en());
+        assert_formats("prefix !sh", Request::SetPrefix("!sh".to_owned()), Tz::UTC, &en());
+        assert_formats(r#"prefix \"sh\\"#,
+                       Request::SetPrefix("\"sh\\".to_owned()),
+                       Tz::UTC,
+                       &en());
+        assert_formats("language", Request::ShowLanguage, Tz::UTC, &en());
+        assert_formats("language Deutsch",
+                       Request::SetLanguage(Language::German),
+                       Tz::UTC,
+                       &en());
+    }
+
+    #[test]
+    fn german() {
+        let de = Vocabulary::new(Language::German);
+        assert_formats("hilfe", Request::Help(None), Tz::UTC, &de);
+        assert_formats("hilfe nicht will",
+                       Request::Help(Some(Command::DontWant)),
+                       Tz::UTC,
+                       &de);
+        assert_formats("will 6 always",
+                       want(&[Tier::Tier6], Timeframe::Always),
+                       Tz::UTC,
+                       &de);
+        assert_formats("nicht will 8",
+                       Request::DontWant {
+                           tiers: vec![Tier::Tier8].into_iter().collect(),
+                           kinds: Default::default(),
+                       },
+                       Tz::UTC,
+                       &de);
+    }
+
+    #[test]
+    fn not_commands() {
+        assert_eq!(None, format_request(&Request::None, &now(), Tz::UTC, &en()));
+        assert_eq!(None, format_request(&Request::Unknown, &now(), Tz::UTC, &en()));
+    }
+}
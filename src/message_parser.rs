@@ -1,60 +1,368 @@
 use std::collections::HashSet;
 use time;
 use time::Duration;
-use discord::model::Message;
-use common::SplitWhitespaceWithRest;
-use model::{Tier, Timeframe, Want, Request};
-
-// TODO unhardcode command strings
-pub fn parse_message(msg: &Message) -> Request {
-    let mut tokens = SplitWhitespaceWithRest::new(&msg.content);
-    let mut previous: Vec<String> = Vec::new();
-    loop {
-        // TODO use matching here once slice matching becomes stable (don't want to use nightly}
-        if previous == vec![] as Vec<String> {
-            match tokens.next() {
-                None => return Request::None,
-                Some(token) => {
-                    match &*token.to_lowercase() {
-                        "" => return Request::None,
-                        "help" => return Request::Help,
-                        "want" => return parse_want(tokens),
-                        "status" => return Request::Status,
-                        "dont" | "don't" => previous.push("dont".to_owned()),
-                        _ => return Request::Unknown,
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use common;
+use common::{Token, Tokenizer, Tokens};
+use discord::model::UserId;
+use model::{Command, Tier, Timeframe, TimeframeKind, Want, Request, TIERS};
+use timezone;
+use vocabulary::{Vocabulary, Keyword, Language};
+use commands::{CommandSpec, COMMANDS};
+
+/// Maximum number of characters of a command prefix.
+pub const MAX_PREFIX_LEN: usize = 10;
+
+/// If the message is addressed at the bot, i.e. starts with the prefix or a mention of the bot,
+/// returns the rest of it.
+pub fn strip_trigger(content: &str, prefix: &str, me: UserId) -> Option<String> {
+    let (first, rest) = common::str_head_tail(content);
+    // Mentions of users with a nickname have an exclamation mark.
+    if first == prefix || first == format!("<@{}>", me.0) || first == format!("<@!{}>", me.0) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Words besides the keywords the parser understands, which are considered for suggestions.
+const PARAMETER_WORDS: [&'static str; 11] = ["always", "until", "logout", "tomorrow", "from", "to",
+                                             "every", "for", "tonight", "after", "till"];
+
+/// If the message is unknown or invalid because of a mistyped word, returns the message with the
+/// word corrected, e.g. "want 10" for "wnat 10". Only corrections that make the message valid are
+/// suggested, the closest one (to a keyword first, if there's a tie) wins.
+pub fn suggest(content: &str, now: time::Tm, tz: Tz, vocabulary: &Vocabulary) -> Option<String> {
+    let is_valid = |content: &str| match parse_message(content, now, tz, vocabulary) {
+        Request::None | Request::Unknown | Request::Invalid { .. } => false,
+        _ => true,
+    };
+    let position = match parse_message(content, now, tz, vocabulary) {
+        // The first token isn't a keyword.
+        Request::Unknown => content.find(|c: char| !c.is_whitespace()).unwrap_or(content.len()),
+        Request::Invalid { position, .. } => position,
+        _ => return None,
+    };
+    let token = match Tokenizer::new(content)
+        .filter_map(Result::ok)
+        .find(|token| token.start == position) {
+        Some(token) => token,
+        None => return None,
+    };
+    let word = &*token.text;
+    // Everything is close to very short words, so they're left out.
+    let word_len = word.chars().count();
+    if word_len < 3 {
+        return None;
+    }
+    let max_distance = if word_len <= 5 { 1 } else { 2 };
+    let mut candidates = vocabulary.all_words()
+        .into_iter()
+        .chain(PARAMETER_WORDS.iter().cloned())
+        .filter(|candidate| candidate.chars().count() >= 3)
+        .map(|candidate| (common::edit_distance(word, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= max_distance)
+        .collect::<Vec<(usize, &str)>>();
+    // The sort is stable, so keywords stay first among equally close words.
+    candidates.sort_by_key(|&(distance, _)| distance);
+    candidates.into_iter()
+        .map(|(_, candidate)| {
+            format!("{}{}{}",
+                    &content[..token.start],
+                    candidate,
+                    &content[token.end..])
+        })
+        .find(|corrected| is_valid(corrected))
+}
+
+/// What parsers need to know besides the tokens.
+pub struct ParseContext<'a> {
+    /// The time relative times in the message are relative to.
+    pub now: time::Tm,
+    /// The timezone times of day in the message are in.
+    pub tz: Tz,
+    /// The keywords in arguments (e.g. commands given to help) are looked up in.
+    pub vocabulary: &'a Vocabulary,
+}
+
+/// A message parsed into a request.
+pub struct Parsed {
+    /// The command the message starts with, None if it doesn't start with one.
+    pub spec: Option<&'static CommandSpec>,
+    /// Whether anything follows the command's keywords.
+    pub has_arguments: bool,
+    pub request: Request,
+}
+
+/// Parses the content of a message into a request. Keywords are looked up in the vocabulary, times
+/// in the message are relative to `now` and in the timezone `tz`.
+pub fn parse_message(content: &str, now: time::Tm, tz: Tz, vocabulary: &Vocabulary) -> Request {
+    parse_command(content, now, tz, vocabulary).request
+}
+
+/// Like `parse_message()`, but also returns the command the message starts with.
+pub fn parse_command(content: &str, now: time::Tm, tz: Tz, vocabulary: &Vocabulary) -> Parsed {
+    // The tokens up to a quote that isn't closed, if there is one.
+    let mut tokenized = Vec::new();
+    let mut quote_error = None;
+    for token in Tokenizer::new(content) {
+        match token {
+            Ok(token) => tokenized.push(token),
+            Err(err) => quote_error = Some(err),
+        }
+    }
+    let mut tokens = Tokens::new(content, &tokenized);
+    let (keyword, first) = match tokens.next() {
+        None if quote_error.is_some() => return Parsed::new(None, Request::Unknown),
+        None => return Parsed::new(None, Request::None),
+        Some(token) => {
+            let keyword = vocabulary.keyword(token);
+            match keyword.and_then(|keyword| first_command(keyword).map(|spec| (keyword, spec))) {
+                Some(found) => found,
+                None => return Parsed::new(None, Request::Unknown),
+            }
+        }
+    };
+    let found = find_command(keyword, &tokens, vocabulary);
+    if let Some((reason, position)) = quote_error {
+        let spec = found.map_or(first, |(spec, _)| spec);
+        return Parsed::new(Some(spec), invalid(spec.command, reason, position));
+    }
+    let (spec, tokens) = match found {
+        Some(found) => found,
+        None => {
+            // The keyword starts a command, but the command's other keywords don't follow.
+            let spec = first;
+            let position = tokens.next_index();
+            let expected = format!("Expected \"{}\" after \"{}\"",
+                                   vocabulary.word(spec.keywords[1]),
+                                   vocabulary.word(keyword));
+            let reason = match tokens.next() {
+                Some(token) => format!("{}, got \"{}\".", expected, token),
+                None => format!("{}.", expected),
+            };
+            return Parsed::new(Some(spec), invalid(spec.command, reason, position));
+        }
+    };
+    let ctx = ParseContext {
+        now: now,
+        tz: tz,
+        vocabulary: vocabulary,
+    };
+    let has_arguments = tokens.rest().is_some();
+    let request = (spec.parse)(tokens, &ctx);
+    Parsed {
+        spec: Some(spec),
+        has_arguments: has_arguments,
+        request: request,
+    }
+}
+
+impl Parsed {
+    fn new(spec: Option<&'static CommandSpec>, request: Request) -> Self {
+        Parsed {
+            spec: spec,
+            has_arguments: false,
+            request: request,
+        }
+    }
+}
+
+/// Returns the command the keyword is the first keyword of, None for keywords that don't start
+/// one (e.g. "remove").
+fn first_command(keyword: Keyword) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.keywords[0] == keyword)
+}
+
+/// Finds the command that starts with the keyword and the tokens: of the commands with the
+/// keyword, the one with the most keywords that all follow. Returns it and the tokens after its
+/// keywords.
+fn find_command<'a>(keyword: Keyword,
+                    tokens: &Tokens<'a>,
+                    vocabulary: &Vocabulary)
+                    -> Option<(&'static CommandSpec, Tokens<'a>)> {
+    COMMANDS.iter()
+        .filter(|spec| spec.keywords.contains(&keyword))
+        .filter_map(|spec| {
+            let mut rest = tokens.clone();
+            let mut missing =
+                spec.keywords.iter().filter(|&&k| k != keyword).collect::<Vec<&Keyword>>();
+            while !missing.is_empty() {
+                let next = rest.next().and_then(|token| vocabulary.keyword(token));
+                match missing.iter().position(|&&k| Some(k) == next) {
+                    Some(i) => {
+                        missing.remove(i);
                     }
+                    None => return None,
                 }
             }
-        } else if previous == vec!["dont"] {
+            Some((spec, rest))
+        })
+        .max_by_key(|&(spec, _)| spec.keywords.len())
+}
+
+pub fn parse_help(mut tokens: Tokens, ctx: &ParseContext) -> Request {
+    let position = tokens.next_index();
+    let token = match tokens.next() {
+        None => return Request::Help(None),
+        Some(token) => token,
+    };
+    let found = ctx.vocabulary
+        .keyword(token)
+        .and_then(|keyword| find_command(keyword, &tokens, ctx.vocabulary));
+    let (spec, mut tokens) = match found {
+        Some(found) => found,
+        None => {
+            let names = COMMANDS.iter()
+                .map(|spec| spec.name(ctx.vocabulary))
+                .collect::<Vec<String>>()
+                .join(", ");
+            return invalid(Command::Help,
+                           format!("Unknown command \"{}\", expected one of {}.", token, names),
+                           position);
+        }
+    };
+    let position = tokens.next_index();
+    if let Some(token) = tokens.next() {
+        return invalid(Command::Help,
+                       format!("Unexpected \"{}\" after the command.", token),
+                       position);
+    }
+    Request::Help(Some(spec.command))
+}
+
+pub fn parse_status(_: Tokens, _: &ParseContext) -> Request {
+    Request::Status
+}
+
+pub fn parse_me(_: Tokens, _: &ParseContext) -> Request {
+    Request::ShowMe
+}
+
+pub fn parse_language(mut tokens: Tokens, _: &ParseContext) -> Request {
+    let position = tokens.next_index();
+    let language = match tokens.next() {
+        None => return Request::ShowLanguage,
+        Some(name) => {
+            match Language::parse(name) {
+                Ok(language) => language,
+                Err(reason) => return invalid(Command::Language, reason, position),
+            }
+        }
+    };
+    let position = tokens.next_index();
+    if let Some(token) = tokens.next() {
+        return invalid(Command::Language,
+                       format!("Unexpected \"{}\" after the language.", token),
+                       position);
+    }
+    Request::SetLanguage(language)
+}
+
+pub fn parse_timezone(mut tokens: Tokens, _: &ParseContext) -> Request {
+    let position = tokens.next_index();
+    let timezone = match tokens.next() {
+        None => return Request::ShowTimezone,
+        Some(name) => {
+            match timezone::parse_timezone(name) {
+                Ok(timezone) => timezone,
+                Err(reason) => return invalid(Command::Timezone, reason, position),
+            }
+        }
+    };
+    let position = tokens.next_index();
+    if let Some(token) = tokens.next() {
+        return invalid(Command::Timezone,
+                       format!("Unexpected \"{}\" after the timezone.", token),
+                       position);
+    }
+    Request::SetTimezone(timezone)
+}
+
+pub fn parse_prefix(mut tokens: Tokens, _: &ParseContext) -> Request {
+    let position = tokens.next_index();
+    let prefix = match tokens.next() {
+        None => return Request::ShowPrefix,
+        Some(prefix) => prefix,
+    };
+    if prefix.chars().count() > MAX_PREFIX_LEN {
+        return invalid(Command::Prefix,
+                       format!("The prefix can be at most {} characters long.", MAX_PREFIX_LEN),
+                       position);
+    }
+    if prefix.is_empty() {
+        return invalid(Command::Prefix, "The prefix can't be empty.", position);
+    }
+    if prefix.contains(char::is_whitespace) {
+        return invalid(Command::Prefix, "The prefix can't contain spaces.", position);
+    }
+    if prefix.starts_with("<@") {
+        return invalid(Command::Prefix,
+                       "The prefix can't be a mention, mentioning me always works anyway.",
+                       position);
+    }
+    let position = tokens.next_index();
+    if let Some(token) = tokens.next() {
+        return invalid(Command::Prefix,
+                       format!("Unexpected \"{}\" after the prefix.", token),
+                       position);
+    }
+    Request::SetPrefix(prefix.to_owned())
+}
+
+pub fn parse_schedules(mut tokens: Tokens, ctx: &ParseContext) -> Request {
+    let position = tokens.next_index();
+    let request = match tokens.next() {
+        None => return Request::ListSchedules,
+        Some(token) if ctx.vocabulary.keyword(token) == Some(Keyword::Remove) => {
+            let position = tokens.next_index();
             match tokens.next() {
-                None => return Request::Unknown,
-                Some(token) => {
-                    match &*token.to_lowercase() {
-                        "want" => return Request::DontWant,
-                        _ => return Request::Unknown,
+                Some(number_str) => {
+                    match number_str.parse::<usize>() {
+                        Ok(number) if number > 0 => Request::RemoveSchedule(number),
+                        _ => {
+                            return invalid(Command::Schedules,
+                                           format!("Expected the number of a schedule, got \
+                                                    \"{}\".",
+                                                   number_str),
+                                           position)
+                        }
                     }
                 }
+                None => {
+                    return invalid(Command::Schedules,
+                                   "Expected the number of the schedule to remove.",
+                                   position)
+                }
             }
-        } else {
-            return Request::Unknown;
         }
+        Some(token) => {
+            return invalid(Command::Schedules,
+                           format!("Expected \"{}\", got \"{}\".",
+                                   ctx.vocabulary.word(Keyword::Remove),
+                                   token),
+                           position)
+        }
+    };
+    let position = tokens.next_index();
+    if let Some(token) = tokens.next() {
+        return invalid(Command::Schedules,
+                       format!("Unexpected \"{}\" after the schedule number.", token),
+                       position);
     }
+    request
 }
 
-fn parse_want(mut tokens: SplitWhitespaceWithRest) -> Request {
+pub fn parse_want(mut tokens: Tokens, ctx: &ParseContext) -> Request {
     let mut tiers = HashSet::new();
     loop {
-        match tokens.next() {
-            Some("6") => {
-                tiers.insert(Tier::Tier6);
-            }
-            Some("8") => {
-                tiers.insert(Tier::Tier8);
-            }
-            Some("10") => {
-                tiers.insert(Tier::Tier10);
-            }
-            t @ None | t @ Some(_) => {
+        let position = tokens.next_index();
+        let t = tokens.next();
+        match t.map(parse_tiers) {
+            Some(Ok(Some(parsed))) => tiers.extend(parsed),
+            Some(Err(reason)) => return invalid(Command::Want, reason, position),
+            Some(Ok(None)) | None => {
                 if tiers.is_empty() {
                     // No tiers are specified (either because nothing is specified at all, or only a
                     // timeframe is specified), assume all are OK.
@@ -67,47 +375,226 @@ fn parse_want(mut tokens: SplitWhitespaceWithRest) -> Request {
                     // parsed as timeframe.
                     tokens.rewind();
                 }
-                return parse_want_timeframe(tokens, tiers);
+                return parse_want_timeframe(tokens, tiers, ctx);
             }
         }
     }
 }
 
-fn parse_want_timeframe(mut tokens: SplitWhitespaceWithRest, mut tiers: HashSet<Tier>) -> Request {
-    let timeframe = match tokens.next() {
-        None => Timeframe::UntilLogout,
-        Some("always") => Timeframe::Always,
-        Some("until") => {
-            if let Some("logout") = tokens.next() {
-                Timeframe::UntilLogout
-            } else {
-                // TODO invalid command instead of unknown: started out right (with want and tiers),
-                // but didn't specify timeframe correctly
-                return Request::Unknown;
-            }
-        }
-        Some("for") => {
-            if let Some(time_str) = tokens.next() {
-                if let Ok(duration) = parse_duration(time_str) {
-                    Timeframe::Timespan { until: time::now_utc() + duration }
-                } else {
-                    // TODO invalid command instead of unknown: started out right (with want and
-                    // tiers), but didn't specify timeframe correctly
-                    return Request::Unknown;
+/// The ways tiers can be given in wants, with examples of them, as shown in the help. A want may
+/// have several of them, e.g. "6 10".
+pub const TIER_FORMS: [(&'static [&'static str], &'static str); 4] =
+    [(&["6", "8", "10"], "a single tier"),
+     (&["t10", "tier10", "X"], "a single tier, written differently"),
+     (&["8+"], "a tier and the ones above it"),
+     (&["6-8", "all"], "the tiers from one to another, or all of them")];
+
+/// Parses a token specifying tiers: a single tier (see `parse_tier()`), a range like "6-10", a tier
+/// and the ones above it like "8+", or "all". Returns None if the token isn't meant to specify
+/// tiers, and an error if it is, but doesn't, e.g. "7".
+fn parse_tiers(token: &str) -> Result<Option<Vec<Tier>>, String> {
+    let token = token.to_lowercase();
+    if token == "all" {
+        return Ok(Some(TIERS.to_vec()));
+    }
+    if !looks_like_tiers(&token) {
+        return Ok(None);
+    }
+    let unknown = || {
+        format!("Unknown tier \"{}\", expected 6, 8 or 10 (or e.g. 8+, 6-10, t10, VIII or all).",
+                token)
+    };
+    let index = |tier: &str| {
+        parse_tier(tier)
+            .and_then(|tier| TIERS.iter().position(|&t| t == tier))
+            .ok_or_else(&unknown)
+    };
+    let (from, until) = if token.ends_with('+') {
+        (try!(index(&token[..token.len() - 1])), TIERS.len() - 1)
+    } else if let Some(i) = token.find('-') {
+        (try!(index(&token[..i])), try!(index(&token[i + 1..])))
+    } else {
+        let i = try!(index(&token));
+        (i, i)
+    };
+    if from > until {
+        return Err(format!("The tier range \"{}\" is empty, the lower tier has to come first.",
+                           token));
+    }
+    Ok(Some(TIERS[from..until + 1].to_vec()))
+}
+
+/// Parses a single tier: its number, optionally preceded by "t" or "tier", or its number as a
+/// roman numeral. The token has to be lowercase.
+fn parse_tier(token: &str) -> Option<Tier> {
+    let number = if token.starts_with("tier") {
+        &token[4..]
+    } else if token.starts_with('t') {
+        &token[1..]
+    } else {
+        token
+    };
+    match number {
+        "6" | "vi" => Some(Tier::Tier6),
+        "8" | "viii" => Some(Tier::Tier8),
+        "10" | "x" => Some(Tier::Tier10),
+        _ => None,
+    }
+}
+
+/// Whether the lowercase token is meant to specify tiers, i.e. starts with a number, "t" or "tier"
+/// and a number, or consists of roman numerals, possibly followed by a range.
+fn looks_like_tiers(token: &str) -> bool {
+    let number = token.trim_left_matches("tier").trim_left_matches('t');
+    number.starts_with(|c: char| c.is_digit(10)) ||
+    (!token.is_empty() &&
+     token.chars().all(|c| c == 'i' || c == 'v' || c == 'x' || c == '-' || c == '+'))
+}
+
+fn expected_tier_or_timeframe(token: &str) -> String {
+    format!("Expected a tier (6, 8 or 10) or a timeframe (always, until, from, every or for), got \
+             \"{}\".",
+            token)
+}
+
+/// The words that stand for kinds of timeframes after "dont want", the kinds they stand for and
+/// what those are, as shown in the help.
+pub static TIMEFRAME_KINDS: [(&'static [&'static str], &'static [TimeframeKind], &'static str);
+                             5] =
+    [(&["always"], &[TimeframeKind::Always], "wants until you take them back"),
+     (&["until", "till", "til"],
+      &[TimeframeKind::UntilLogout, TimeframeKind::Timespan],
+      "wants until you log out or until a time"),
+     (&["for"], &[TimeframeKind::Timespan], "wants until a time"),
+     (&["from"], &[TimeframeKind::Window], "wants from one time to another"),
+     (&["every"], &[TimeframeKind::Weekly], "weekly schedules")];
+
+/// Parses what comes after "dont want": the tiers and the kinds of timeframes to remove the wants
+/// of, see `TIMEFRAME_KINDS`.
+pub fn parse_dont_want(mut tokens: Tokens, _: &ParseContext) -> Request {
+    let mut tiers = HashSet::new();
+    let mut kinds = HashSet::new();
+    loop {
+        let position = tokens.next_index();
+        let token = match tokens.next() {
+            None => break,
+            Some(token) => token,
+        };
+        match TIMEFRAME_KINDS.iter().find(|&&(words, _, _)| is_one_of(token, words)) {
+            Some(&(_, given, _)) => kinds.extend(given.iter().cloned()),
+            None => {
+                match parse_tiers(token) {
+                    Ok(Some(parsed)) => tiers.extend(parsed),
+                    Err(reason) => return invalid(Command::DontWant, reason, position),
+                    Ok(None) => {
+                        return invalid(Command::DontWant,
+                                       expected_tier_or_timeframe(token),
+                                       position)
+                    }
                 }
-            } else {
-                // TODO invalid command instead of unknown: started out right (with want and tiers),
-                // but didn't specify timeframe correctly
-                return Request::Unknown;
             }
         }
-        Some(_) => {
-            // TODO invalid command instead of unknown: started out right (with want and tiers),
-            // but didn't specify timeframe correctly
-            return Request::Unknown;
-        }
+    }
+    Request::DontWant {
+        tiers: tiers,
+        kinds: kinds,
+    }
+}
 
+/// Phrases understood in wants as the regular timeframes they stand for, in the order they're
+/// listed in the help. They're looked for before the regular timeframes, ignoring case. None of
+/// them is the start of another.
+pub const TIME_PHRASES: [(&'static str, &'static str); 10] =
+    [("tonight", "from 18:00 to 00:00"),
+     ("for an hour", "for 1h"),
+     ("for the next hour", "for 1h"),
+     ("for half an hour", "for 30m"),
+     ("for a couple hours", "for 2h"),
+     ("for a couple of hours", "for 2h"),
+     ("for the next couple hours", "for 2h"),
+     ("for the next couple of hours", "for 2h"),
+     ("for a few hours", "for 3h"),
+     ("for the next few hours", "for 3h")];
+
+/// A timeframe a want can be given in: the words it starts with, how it's written and what it
+/// means, as shown in the help, and how it's parsed.
+pub struct TimeframeSpec {
+    /// The words the timeframe starts with, the first one being the one shown in the help.
+    pub keywords: &'static [&'static str],
+    /// How the timeframe is written, in the format of `CommandSpec::arguments`.
+    pub syntax: &'static str,
+    pub description: &'static str,
+    /// Parses what follows the keyword, given where the keyword is. Returns the reason and
+    /// position of the offending token on error.
+    parse: fn(&mut Tokens, usize, &ParseContext) -> Result<Timeframe, (String, usize)>,
+}
+
+/// All timeframes a want can be given in besides `TIME_PHRASES`, in the order they're listed in
+/// the help. Without one, a want lasts until logout.
+pub static TIMEFRAMES: [TimeframeSpec; 6] =
+    [TimeframeSpec {
+         keywords: &["always"],
+         syntax: "always",
+         description: "until you take it back",
+         parse: parse_always,
+     },
+     TimeframeSpec {
+         keywords: &["until", "till", "til"],
+         syntax: "until logout | until [tomorrow] <time>",
+         description: "until you log out (the default), or until the time",
+         parse: parse_until,
+     },
+     TimeframeSpec {
+         keywords: &["for"],
+         syntax: "for <duration>",
+         description: "for the duration from now",
+         parse: parse_for,
+     },
+     TimeframeSpec {
+         keywords: &["from"],
+         syntax: "from [tomorrow] <time> to <time>",
+         description: "once, from one time to the other",
+         parse: parse_from,
+     },
+     TimeframeSpec {
+         keywords: &["after"],
+         syntax: "after <time>",
+         description: "from the time to midnight",
+         parse: parse_after,
+     },
+     TimeframeSpec {
+         keywords: &["every"],
+         syntax: "every <days> <time>-<time>",
+         description: "every week on the days, from one time to the other",
+         parse: parse_every,
+     }];
+
+/// How the values in timeframes are written, with examples of them, as shown in the help.
+pub const VALUE_FORMATS: [(&'static str, &'static [&'static str]); 3] =
+    [("<time>", &["22:30", "8pm", "10:30am", "midnight", "noon"]),
+     ("<duration>", &["1h30m", "90m", "1.5h", "45min", "1:30h", "2 hours"]),
+     ("<days>", &["mon,wed,fri", "mon-fri", "weekdays", "weekends", "day"])];
+
+fn parse_want_timeframe(mut tokens: Tokens,
+                        mut tiers: HashSet<Tier>,
+                        ctx: &ParseContext)
+                        -> Request {
+    let position = tokens.next_index();
+    let parsed = match skip_time_phrase(&mut tokens) {
+        Some(meaning) => parse_time_phrase(meaning, position, ctx),
+        None if tokens.clone().next().is_none() => Ok(Timeframe::UntilLogout),
+        None => parse_timeframe(&mut tokens, ctx),
+    };
+    let timeframe = match parsed {
+        Ok(timeframe) => timeframe,
+        Err((reason, position)) => return invalid(Command::Want, reason, position),
     };
+    let position = tokens.next_index();
+    if let Some(token) = tokens.next() {
+        return invalid(Command::Want,
+                       format!("Unexpected \"{}\" after the timeframe.", token),
+                       position);
+    }
     let wants = tiers.drain()
         .map(|tier| Want { tier: tier })
         .collect();
@@ -117,8 +604,518 @@ fn parse_want_timeframe(mut tokens: SplitWhitespaceWithRest, mut tiers: HashSet<
     }
 }
 
+/// Parses one of `TIMEFRAMES`. Returns the reason and position of the offending token on error.
+fn parse_timeframe(tokens: &mut Tokens, ctx: &ParseContext) -> Result<Timeframe, (String, usize)> {
+    let position = tokens.next_index();
+    let token = try!(tokens.next().ok_or_else(|| {
+        ("Expected a timeframe.".to_owned(), position)
+    }));
+    match TIMEFRAMES.iter().find(|spec| is_one_of(token, spec.keywords)) {
+        Some(spec) => (spec.parse)(tokens, position, ctx),
+        None => Err((expected_tier_or_timeframe(token), position)),
+    }
+}
+
+/// If the tokens start with one of `TIME_PHRASES`, advances them past it and returns the regular
+/// timeframe it stands for.
+fn skip_time_phrase(tokens: &mut Tokens) -> Option<&'static str> {
+    let starts_with = |phrase: &str| {
+        let mut words = tokens.clone();
+        phrase.split_whitespace()
+            .all(|word| words.next().map_or(false, |token| token.eq_ignore_ascii_case(word)))
+    };
+    let (phrase, meaning) = match TIME_PHRASES.iter().find(|&&(phrase, _)| starts_with(phrase)) {
+        Some(&(phrase, meaning)) => (phrase, meaning),
+        None => return None,
+    };
+    for _ in phrase.split_whitespace() {
+        tokens.next();
+    }
+    Some(meaning)
+}
+
+/// Parses the regular timeframe a phrase at the position stands for. The meanings are valid, so
+/// errors (e.g. a time that doesn't exist in the timezone) are reported at the phrase.
+fn parse_time_phrase(meaning: &'static str,
+                     position: usize,
+                     ctx: &ParseContext)
+                     -> Result<Timeframe, (String, usize)> {
+    let tokenized = Tokenizer::new(meaning).filter_map(Result::ok).collect::<Vec<Token>>();
+    parse_timeframe(&mut Tokens::new(meaning, &tokenized), ctx)
+        .map_err(|(reason, _)| (reason, position))
+}
+
+fn parse_always(_: &mut Tokens, _: usize, _: &ParseContext) -> Result<Timeframe, (String, usize)> {
+    Ok(Timeframe::Always)
+}
+
+/// Parses what comes after "for": a duration, either as a number and a unit in words (see
+/// `parse_duration_phrase()`) or as numbers with units (see `parse_duration()`).
+fn parse_for(tokens: &mut Tokens,
+             _: usize,
+             ctx: &ParseContext)
+             -> Result<Timeframe, (String, usize)> {
+    let position = tokens.next_index();
+    let mut phrase = tokens.clone();
+    if let Some(duration) = parse_duration_phrase(&mut phrase) {
+        *tokens = phrase;
+        return Ok(Timeframe::Timespan { until: ctx.now + duration });
+    }
+    let time_str = try!(tokens.next().ok_or_else(|| {
+        ("Expected a duration after \"for\".".to_owned(), position)
+    }));
+    let duration = try!(parse_duration(time_str).map_err(|reason| (reason, position)));
+    // Further in the future than times can be shown.
+    if !timezone::in_range(&(ctx.now + duration)) {
+        return Err(("Given duration is too large.".to_owned(), position));
+    }
+    Ok(Timeframe::Timespan { until: ctx.now + duration })
+}
+
+/// Parses what comes after "until": either "logout", or a time of day in the timezone, optionally
+/// preceded by "tomorrow". A time of day without "tomorrow" that has already passed today is taken
+/// to mean tomorrow. Returns the reason and position of the offending token on error.
+fn parse_until(tokens: &mut Tokens,
+               _: usize,
+               ctx: &ParseContext)
+               -> Result<Timeframe, (String, usize)> {
+    let (now, tz) = (ctx.now, ctx.tz);
+    let expected = "Expected \"logout\", a time (e.g. 22:30) or \"tomorrow\" and a time after \
+                    \"until\"";
+    let position = tokens.next_index();
+    let (time_str, tomorrow, position) = match tokens.next() {
+        Some(token) if token.eq_ignore_ascii_case("logout") => return Ok(Timeframe::UntilLogout),
+        Some(token) if token.eq_ignore_ascii_case("tomorrow") => {
+            let position = tokens.next_index();
+            match tokens.next() {
+                Some(time_str) => (time_str, true, position),
+                None => return Err(("Expected a time after \"tomorrow\".".to_owned(), position)),
+            }
+        }
+        Some(time_str) => {
+            if !time_str.contains(':') && parse_time_of_day(time_str).is_err() {
+                return Err((format!("{}, got \"{}\".", expected, time_str), position));
+            }
+            (time_str, false, position)
+        }
+        None => return Err((format!("{}.", expected), position)),
+    };
+    let (hours, minutes) = try!(parse_time_of_day(time_str).map_err(|reason| (reason, position)));
+    let today = try!(local_today(&now, tz, position));
+    let nonexistent = || {
+        (format!("{} doesn't exist in timezone {} on that day.", time_str, tz.name()), position)
+    };
+    let until = match timezone::from_local(today, hours, minutes, tz) {
+        Some(until) if !tomorrow && until > now => until,
+        // Tomorrow was requested, or the time has already passed today (or doesn't exist today).
+        _ => {
+            let tomorrow = try!(next_day(today, position));
+            try!(timezone::from_local(tomorrow, hours, minutes, tz).ok_or_else(nonexistent))
+        }
+    };
+    Ok(Timeframe::Timespan { until: until })
+}
+
+/// Parses what comes after "from": a start time of day in the timezone, optionally preceded by
+/// "tomorrow", followed by "to" (or "until") and an end time of day, see `resolve_window()`.
+/// Returns the reason and position of the offending token on error.
+fn parse_from(tokens: &mut Tokens,
+              _: usize,
+              ctx: &ParseContext)
+              -> Result<Timeframe, (String, usize)> {
+    let position = tokens.next_index();
+    let (from_str, tomorrow, from_position) = match tokens.next() {
+        Some(token) if token.eq_ignore_ascii_case("tomorrow") => {
+            let position = tokens.next_index();
+            match tokens.next() {
+                Some(time_str) => (time_str, true, position),
+                None => return Err(("Expected a time after \"tomorrow\".".to_owned(), position)),
+            }
+        }
+        Some(time_str) => (time_str, false, position),
+        None => {
+            return Err(("Expected a time (e.g. 20:00) or \"tomorrow\" and a time after \"from\"."
+                            .to_owned(),
+                        position))
+        }
+    };
+    let from = try!(GivenTime::parse(from_str, from_position));
+    let position = tokens.next_index();
+    match tokens.next() {
+        Some(token) if is_one_of(token, &["to", "until", "till", "til"]) => {}
+        Some(token) => {
+            return Err((format!("Expected \"to\" and an end time after the start time, got \
+                                 \"{}\".",
+                                token),
+                        position))
+        }
+        None => {
+            return Err(("Expected \"to\" and an end time after the start time.".to_owned(),
+                        position))
+        }
+    }
+    let until_position = tokens.next_index();
+    let until_str = try!(tokens.next().ok_or_else(|| {
+        ("Expected an end time (e.g. 23:00).".to_owned(), until_position)
+    }));
+    let until = try!(GivenTime::parse(until_str, until_position));
+    resolve_window(&from, &until, tomorrow, ctx.now, ctx.tz)
+}
+
+/// Parses what comes after "after": a time of day in the timezone, the start of a window that
+/// lasts until midnight.
+fn parse_after(tokens: &mut Tokens,
+               _: usize,
+               ctx: &ParseContext)
+               -> Result<Timeframe, (String, usize)> {
+    let position = tokens.next_index();
+    let from_str = try!(tokens.next().ok_or_else(|| {
+        ("Expected a time (e.g. 20:00 or 8pm) after \"after\".".to_owned(), position)
+    }));
+    let from = try!(GivenTime::parse(from_str, position));
+    let until = GivenTime::new("midnight", position, (0, 0));
+    resolve_window(&from, &until, false, ctx.now, ctx.tz)
+}
+
+/// A time of day as given in a message, with where it was given for error messages.
+struct GivenTime<'a> {
+    text: &'a str,
+    position: usize,
+    hours: u32,
+    minutes: u32,
+}
+
+impl<'a> GivenTime<'a> {
+    fn new(text: &'a str, position: usize, (hours, minutes): (u32, u32)) -> Self {
+        GivenTime {
+            text: text,
+            position: position,
+            hours: hours,
+            minutes: minutes,
+        }
+    }
+
+    /// Parses a time of day, see `parse_time_of_day()`.
+    fn parse(text: &'a str, position: usize) -> Result<Self, (String, usize)> {
+        parse_time_of_day(text)
+            .map(|hours_minutes| GivenTime::new(text, position, hours_minutes))
+            .map_err(|reason| (reason, position))
+    }
+
+    /// Returns the point in time of the time of day on the date in the timezone.
+    fn on(&self, date: NaiveDate, tz: Tz) -> Result<time::Tm, (String, usize)> {
+        timezone::from_local(date, self.hours, self.minutes, tz).ok_or_else(|| {
+            (format!("{} doesn't exist in timezone {} on that day.", self.text, tz.name()),
+             self.position)
+        })
+    }
+}
+
+/// Returns the current date in the timezone, or an error at the position if there is none, because
+/// the current time is out of range.
+fn local_today(now: &time::Tm, tz: Tz, position: usize) -> Result<NaiveDate, (String, usize)> {
+    timezone::local_date(now, tz).ok_or_else(|| {
+        (format!("The current time can't be shown in timezone {}.", tz.name()), position)
+    })
+}
+
+/// Returns the day after the date, or an error at the position if it's out of range.
+fn next_day(date: NaiveDate, position: usize) -> Result<NaiveDate, (String, usize)> {
+    date.succ_opt().ok_or_else(|| ("The day after that is out of range.".to_owned(), position))
+}
+
+/// Returns the window from one time of day to another in the timezone, today or tomorrow. An end
+/// time that isn't after the start time is taken to be on the next day. A window that isn't
+/// requested for tomorrow, but has already ended today is taken to mean tomorrow.
+fn resolve_window(from: &GivenTime,
+                  until: &GivenTime,
+                  tomorrow: bool,
+                  now: time::Tm,
+                  tz: Tz)
+                  -> Result<Timeframe, (String, usize)> {
+    let window_on = |date: NaiveDate| -> Result<(time::Tm, time::Tm), (String, usize)> {
+        let from = try!(from.on(date, tz));
+        let until = match until.on(date, tz) {
+            Ok(until) if until > from => until,
+            _ => try!(until.on(try!(next_day(date, until.position)), tz)),
+        };
+        Ok((from, until))
+    };
+    let today = try!(local_today(&now, tz, from.position));
+    let (from, until) = if tomorrow {
+        try!(window_on(try!(next_day(today, from.position))))
+    } else {
+        match window_on(today) {
+            Ok((from, until)) if until > now => (from, until),
+            // The window has already ended today (or doesn't exist today).
+            _ => try!(window_on(try!(next_day(today, from.position)))),
+        }
+    };
+    Ok(Timeframe::Window {
+        from: from,
+        until: until,
+    })
+}
+
+/// Parses what comes after "every": the days of the week (e.g. "mon,wed,fri" or "mon-fri")
+/// followed by a time range (e.g. "19:00-22:00"). Returns the reason and position of the offending
+/// token on error.
+fn parse_every(tokens: &mut Tokens,
+               _: usize,
+               _: &ParseContext)
+               -> Result<Timeframe, (String, usize)> {
+    let position = tokens.next_index();
+    let days_str = try!(tokens.next().ok_or_else(|| {
+        ("Expected days of the week (e.g. mon,wed,fri or mon-fri) after \"every\".".to_owned(),
+         position)
+    }));
+    let days = try!(parse_weekdays(days_str).map_err(|reason| (reason, position)));
+    let position = tokens.next_index();
+    let range_str = try!(tokens.next().ok_or_else(|| {
+        ("Expected a time range (e.g. 19:00-22:00) after the days.".to_owned(), position)
+    }));
+    let mut split = range_str.splitn(2, '-');
+    let from_str = split.next().unwrap_or("");
+    let until_str = try!(split.next().ok_or_else(|| {
+        (format!("Expected a time range (e.g. 19:00-22:00), got \"{}\".", range_str), position)
+    }));
+    let (from_hours, from_minutes) =
+        try!(parse_time_of_day(from_str).map_err(|reason| (reason, position)));
+    let (until_hours, until_minutes) =
+        try!(parse_time_of_day(until_str).map_err(|reason| (reason, position)));
+    let (from, until) = (from_hours * 60 + from_minutes, until_hours * 60 + until_minutes);
+    if from == until {
+        return Err(("The start and end time must differ.".to_owned(), position));
+    }
+    Ok(Timeframe::Weekly {
+        days: days,
+        from: from,
+        until: until,
+    })
+}
+
+/// Parses comma-separated days of the week or ranges of them (e.g. "mon,wed-fri") into a bitmask,
+/// bit 0 being Monday. "weekdays", "weekends" and "day" (every day) are understood as well.
+fn parse_weekdays(days_str: &str) -> Result<u8, String> {
+    let mut days = 0;
+    for item in days_str.split(',') {
+        let mut range = item.splitn(2, '-');
+        let first = range.next().unwrap_or("");
+        match range.next() {
+            Some(last) => {
+                let first = try!(parse_weekday(first));
+                let last = try!(parse_weekday(last));
+                // Ranges may wrap around the end of the week, e.g. "fri-mon".
+                let mut day = first;
+                loop {
+                    days |= 1 << day;
+                    if day == last {
+                        break;
+                    }
+                    day = (day + 1) % 7;
+                }
+            }
+            None => {
+                days |= match &*first.to_lowercase() {
+                    "weekdays" => 0b0011111,
+                    "weekends" | "weekend" => 0b1100000,
+                    "day" | "days" => 0b1111111,
+                    _ => 1 << try!(parse_weekday(first)),
+                };
+            }
+        }
+    }
+    Ok(days)
+}
+
+/// Parses the (possibly abbreviated) English name of a day of the week, 0 being Monday.
+fn parse_weekday(day_str: &str) -> Result<u8, String> {
+    match &*day_str.to_lowercase() {
+        "mon" | "monday" => Ok(0),
+        "tue" | "tues" | "tuesday" => Ok(1),
+        "wed" | "wednesday" => Ok(2),
+        "thu" | "thur" | "thurs" | "thursday" => Ok(3),
+        "fri" | "friday" => Ok(4),
+        "sat" | "saturday" => Ok(5),
+        "sun" | "sunday" => Ok(6),
+        _ => {
+            Err(format!("\"{}\" is not a day of the week, expected e.g. mon,wed,fri or mon-fri.",
+                        day_str))
+        }
+    }
+}
+
+/// Parses a time of day into hours and minutes: either in the format ("{}:{:02}", hours, minutes)
+/// on the 24-hour clock, on the 12-hour clock (e.g. "8pm" or "10:30am"), or "midnight" or "noon".
+fn parse_time_of_day(time_str: &str) -> Result<(u32, u32), String> {
+    let not_a_time = || format!("\"{}\" is not a valid time, expected e.g. 22:30.", time_str);
+    let lower = time_str.to_lowercase();
+    let (clock_str, pm) = match &*lower {
+        "midnight" => return Ok((0, 0)),
+        "noon" => return Ok((12, 0)),
+        s if s.ends_with("am") => (&s[..s.len() - 2], Some(false)),
+        s if s.ends_with("pm") => (&s[..s.len() - 2], Some(true)),
+        s => (s, None),
+    };
+    let mut split = clock_str.splitn(2, ':');
+    let hours_str = split.next().unwrap_or("");
+    let minutes_str = match split.next() {
+        Some(minutes_str) => minutes_str,
+        // Minutes are optional on the 12-hour clock.
+        None if pm.is_some() => "00",
+        None => "",
+    };
+    let is_digits = |s: &str| !s.is_empty() && s.len() <= 2 && s.chars().all(|c| c.is_digit(10));
+    if !is_digits(hours_str) || minutes_str.len() != 2 || !is_digits(minutes_str) {
+        return Err(not_a_time());
+    }
+    // Only up to two digits at this point, so parsing can't fail.
+    let hours = hours_str.parse::<u32>().unwrap_or(0);
+    let minutes = minutes_str.parse::<u32>().unwrap_or(0);
+    let hours = match pm {
+        None if hours <= 23 => hours,
+        // 12am is midnight, 12pm noon.
+        Some(pm) if hours >= 1 && hours <= 12 => hours % 12 + if pm { 12 } else { 0 },
+        _ => return Err(not_a_time()),
+    };
+    if minutes > 59 {
+        return Err(not_a_time());
+    }
+    Ok((hours, minutes))
+}
+
+/// Whether the token is one of the words, ignoring case.
+fn is_one_of(token: &str, words: &[&str]) -> bool {
+    words.iter().any(|word| token.eq_ignore_ascii_case(word))
+}
+
+fn invalid<S: Into<String>>(command: Command, reason: S, position: usize) -> Request {
+    Request::Invalid {
+        command: command,
+        reason: reason.into(),
+        position: position,
+    }
+}
+
+/// Parses a duration given as a whole number and a unit, like "90 minutes" or "2 hours",
+/// optionally preceded by "the next" (e.g. "the next 3 minutes"). Phrases without a number are
+/// in `TIME_PHRASES`. Returns None if the tokens don't start with such a duration, the tokens are
+/// only advanced past it if they do.
+fn parse_duration_phrase(tokens: &mut Tokens) -> Option<Duration> {
+    let words = tokens.clone().take(4).map(str::to_lowercase).collect::<Vec<String>>();
+    let word = |i: usize| words.get(i).map_or("", |word| &**word);
+    // "the next" doesn't change the meaning.
+    let i = if word(0) == "the" && word(1) == "next" { 2 } else { 0 };
+    let number = word(i);
+    if number.is_empty() || !number.chars().all(|c| c.is_digit(10)) {
+        return None;
+    }
+    // Keeps far from overflowing, the regular durations are for anything larger.
+    let number = match number.parse::<i64>() {
+        Ok(number) if number <= 10000 => number,
+        _ => return None,
+    };
+    let unit_secs = match word(i + 1) {
+        "hour" | "hours" => 3600,
+        "minute" | "minutes" | "min" | "mins" => 60,
+        _ => return None,
+    };
+    for _ in 0..i + 2 {
+        tokens.next();
+    }
+    Some(Duration::seconds(number * unit_secs))
+}
+
+/// Parses a duration, which is either a sequence of numbers with units (e.g. "2h", "90m", "1h30m",
+/// "1.5h", "45min") or in the format ("{}:{}h", hours, minutes).
+///
+/// Hours can be given as "h", "hr", "hrs", "hour" or "hours", minutes as "m", "min", "mins",
+/// "minute" or "minutes". A number without a unit following hours is taken as minutes ("1h30").
+fn parse_duration(duration_str: &str) -> Result<Duration, String> {
+    if duration_str.starts_with('-') {
+        return Err("Negative duration given.".to_owned());
+    }
+    if duration_str.contains(':') {
+        return parse_hours_mins(duration_str);
+    }
+    if duration_str.is_empty() {
+        return Err("No duration given.".to_owned());
+    }
+    let is_number_char = |c: char| c.is_digit(10) || c == '.';
+    let mut rest = duration_str;
+    let mut total_secs: i64 = 0;
+    let (mut seen_hours, mut seen_minutes) = (false, false);
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !is_number_char(c)).unwrap_or(rest.len());
+        if number_len == 0 {
+            return Err(format!("Expected a number, got \"{}\".", rest));
+        }
+        let (number_str, after_number) = rest.split_at(number_len);
+        let unit_len = after_number.find(&is_number_char).unwrap_or(after_number.len());
+        let (unit_str, after_unit) = after_number.split_at(unit_len);
+        let unit_secs = match &*unit_str.to_lowercase() {
+            "h" | "hr" | "hrs" | "hour" | "hours" => {
+                if seen_hours {
+                    return Err("Hours given more than once.".to_owned());
+                }
+                seen_hours = true;
+                3600
+            }
+            "m" | "min" | "mins" | "minute" | "minutes" => {
+                if seen_minutes {
+                    return Err("Minutes given more than once.".to_owned());
+                }
+                seen_minutes = true;
+                60
+            }
+            "" if seen_hours && !seen_minutes => {
+                seen_minutes = true;
+                60
+            }
+            "" => return Err(format!("No unit given for \"{}\".", number_str)),
+            _ => return Err(format!("Unknown unit \"{}\".", unit_str)),
+        };
+        let secs = try!(parse_decimal_times(number_str, unit_secs));
+        total_secs = try!(total_secs.checked_add(secs)
+            .ok_or("Given duration is too large.".to_owned()));
+        rest = after_unit;
+    }
+    // Total number of milliseconds in a Duration must not exceed i64::max_value().
+    if total_secs > i64::max_value() / 1000 {
+        return Err("Given duration is too large.".to_owned());
+    }
+    Ok(Duration::seconds(total_secs))
+}
+
+/// Parses a non-negative decimal number and multiplies it by a factor, rounding down.
+fn parse_decimal_times(number_str: &str, factor: i64) -> Result<i64, String> {
+    let not_a_number = || format!("\"{}\" is not a number.", number_str);
+    let too_large = || "Given duration is too large.".to_owned();
+    let mut split = number_str.splitn(2, '.');
+    let int_str = split.next().unwrap_or("");
+    let frac_str = split.next().unwrap_or("");
+    if (int_str.is_empty() && frac_str.is_empty()) || frac_str.contains('.') {
+        return Err(not_a_number());
+    }
+    let int = if int_str.is_empty() {
+        0
+    } else {
+        // Only digits at this point, so the only possible error is overflow.
+        try!(int_str.parse::<i64>().map_err(|_| too_large()))
+    };
+    let mut result = try!(int.checked_mul(factor).ok_or_else(too_large));
+    // More precision than nanoseconds is pointless, and more digits could overflow.
+    let frac_str = &frac_str[..::std::cmp::min(frac_str.len(), 9)];
+    if !frac_str.is_empty() {
+        let frac = try!(frac_str.parse::<i64>().map_err(|_| not_a_number()));
+        let frac_denominator = 10i64.pow(frac_str.len() as u32);
+        result = try!(result.checked_add(frac * factor / frac_denominator).ok_or_else(too_large));
+    }
+    Ok(result)
+}
+
 /// Parses format ("{}:{}h", hours, minutes)
-fn parse_duration(hours_mins_str: &str) -> Result<Duration, String> {
+fn parse_hours_mins(hours_mins_str: &str) -> Result<Duration, String> {
     let mut split = hours_mins_str.split(":");
     let hours = match split.next() {
         None => return Err("No hours given.".to_owned()),
@@ -165,3 +1162,1070 @@ fn parse_duration(hours_mins_str: &str) -> Result<Duration, String> {
     let total_minutes = hours * 60 + minutes;
     Ok(Duration::minutes(total_minutes))
 }
+
+#[cfg(test)]
+mod tests_suggest {
+    use super::suggest;
+    use vocabulary::{Vocabulary, Language};
+    use chrono_tz::Tz;
+    use time;
+
+    fn suggest_en(content: &str) -> Option<String> {
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        suggest(content, now, Tz::UTC, &Vocabulary::new(Language::English))
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(Some("want 10".to_owned()), suggest_en("wnat 10"));
+        assert_eq!(Some("status".to_owned()), suggest_en("stauts"));
+        assert_eq!(Some("help".to_owned()), suggest_en("hlep"));
+        assert_eq!(Some("dont want".to_owned()), suggest_en("dont wnat"));
+        assert_eq!(Some("timezone Europe/Berlin".to_owned()),
+                   suggest_en("timzone Europe/Berlin"));
+    }
+
+    #[test]
+    fn timeframe_words() {
+        assert_eq!(Some("want 10 always".to_owned()), suggest_en("want 10 alwyas"));
+        assert_eq!(Some("want until 22:00".to_owned()), suggest_en("want untill 22:00"));
+        assert_eq!(Some("want  8 for 2h".to_owned()), suggest_en("want  8 fro 2h"));
+        assert_eq!(Some("schedules remove 1".to_owned()), suggest_en("schedules remvoe 1"));
+    }
+
+    #[test]
+    fn only_valid_corrections() {
+        // "wnat" is close to "want", but "want until later" is still invalid.
+        assert_eq!(None, suggest_en("wnat until later"));
+        assert_eq!(None, suggest_en("want 10 for 2x"));
+        assert_eq!(None, suggest_en("want 10 sometime"));
+        assert_eq!(None, suggest_en("hello there"));
+        assert_eq!(None, suggest_en("xy"));
+        assert_eq!(None, suggest_en("want 10"));
+        assert_eq!(None, suggest_en(""));
+    }
+
+    #[test]
+    fn german() {
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        assert_eq!(Some("hilfe".to_owned()),
+                   suggest("hilfr", now, Tz::UTC, &Vocabulary::new(Language::German)));
+    }
+}
+
+#[cfg(test)]
+mod tests_strip_trigger {
+    use super::strip_trigger;
+    use discord::model::UserId;
+
+    #[test]
+    fn prefix() {
+        assert_eq!(Some("want 10".to_owned()), strip_trigger(".sh want 10", ".sh", UserId(1)));
+        assert_eq!(Some("".to_owned()), strip_trigger("!sh", "!sh", UserId(1)));
+        assert_eq!(None, strip_trigger(".sh want 10", "!sh", UserId(1)));
+        assert_eq!(None, strip_trigger(".shwant 10", ".sh", UserId(1)));
+        assert_eq!(None, strip_trigger("want 10", ".sh", UserId(1)));
+    }
+
+    #[test]
+    fn mention() {
+        assert_eq!(Some("status".to_owned()), strip_trigger("<@42> status", ".sh", UserId(42)));
+        assert_eq!(Some("status".to_owned()), strip_trigger("<@!42>  status", ".sh", UserId(42)));
+        assert_eq!(None, strip_trigger("<@43> status", ".sh", UserId(42)));
+        assert_eq!(None, strip_trigger("hey <@42> status", ".sh", UserId(42)));
+    }
+}
+
+#[cfg(test)]
+mod tests_parse_duration {
+    use super::parse_duration;
+    use time::Duration;
+
+    #[test]
+    fn valid() {
+        let cases = vec![("1:30h", Duration::minutes(90)),
+                         ("0:00h", Duration::minutes(0)),
+                         ("12:05h", Duration::minutes(12 * 60 + 5)),
+                         ("2h", Duration::hours(2)),
+                         ("90m", Duration::minutes(90)),
+                         ("45min", Duration::minutes(45)),
+                         ("45mins", Duration::minutes(45)),
+                         ("10minutes", Duration::minutes(10)),
+                         ("1minute", Duration::minutes(1)),
+                         ("1hr", Duration::hours(1)),
+                         ("3hrs", Duration::hours(3)),
+                         ("1hour", Duration::hours(1)),
+                         ("2hours", Duration::hours(2)),
+                         ("2H", Duration::hours(2)),
+                         ("1h30m", Duration::minutes(90)),
+                         ("1h30min", Duration::minutes(90)),
+                         ("1h30", Duration::minutes(90)),
+                         ("30m1h", Duration::minutes(90)),
+                         ("1h75m", Duration::minutes(135)),
+                         ("1.5h", Duration::minutes(90)),
+                         (".5h", Duration::minutes(30)),
+                         ("2.h", Duration::hours(2)),
+                         ("0.25hours", Duration::minutes(15)),
+                         ("1.5m", Duration::seconds(90)),
+                         ("1.1234567891h", Duration::seconds(4044)),
+                         ("0h", Duration::minutes(0))];
+        for (input, expected) in cases {
+            assert_eq!(Ok(expected), parse_duration(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = vec![("", "No duration given."),
+                         ("-1h", "Negative duration given."),
+                         ("-1:30h", "Negative duration given."),
+                         ("1:-30h", "Negative duration given."),
+                         ("1:60h", "Too many minutes given."),
+                         ("1:30", "Duration doesn't end with \"h\"."),
+                         ("1:h", "Minutes are not a positive integer."),
+                         ("x:30h", "Hours are not a positive integer."),
+                         ("1:", "No minutes given."),
+                         ("90", "No unit given for \"90\"."),
+                         ("2d", "Unknown unit \"d\"."),
+                         ("2hx", "Unknown unit \"hx\"."),
+                         ("h", "Expected a number, got \"h\"."),
+                         ("1h2h", "Hours given more than once."),
+                         ("1m2m", "Minutes given more than once."),
+                         ("1h30m5", "No unit given for \"5\"."),
+                         ("1.2.3h", "\"1.2.3\" is not a number."),
+                         (".h", "\".\" is not a number."),
+                         ("ßh", "Expected a number, got \"ßh\".")];
+        for (input, expected) in cases {
+            assert_eq!(Err(expected.to_owned()), parse_duration(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn too_large() {
+        let cases = vec!["9999999999999999999h",
+                         "99999999999999999m",
+                         "2562047788015216h",
+                         "2562047788015215h1000000m",
+                         "99999999999999:00h"];
+        for input in cases {
+            assert_eq!(Err("Given duration is too large.".to_owned()),
+                       parse_duration(input),
+                       "input: {}",
+                       input);
+        }
+        // Just below the limit.
+        assert!(parse_duration("2562047788015h").is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests_parse_message {
+    use super::{parse_message, parse_command, TIME_PHRASES, TIER_FORMS, TIMEFRAMES,
+                TIMEFRAME_KINDS, VALUE_FORMATS};
+    use model::{Command, Request, Tier, Timeframe, TimeframeKind, Want};
+    use vocabulary::{Vocabulary, Language};
+    use std::collections::HashSet;
+    use chrono_tz::Tz;
+    use time;
+
+    /// 2016-09-27 18:13:20 UTC
+    fn now() -> time::Tm {
+        time::at_utc(time::Timespec::new(1475000000, 0))
+    }
+
+    fn en() -> Vocabulary {
+        Vocabulary::new(Language::English)
+    }
+
+    fn de() -> Vocabulary {
+        Vocabulary::new(Language::German)
+    }
+
+    fn until(sec: i64) -> Timeframe {
+        Timeframe::Timespan { until: time::at_utc(time::Timespec::new(sec, 0)) }
+    }
+
+    fn want_all(time: Timeframe) -> Request {
+        let wants = vec![Tier::Tier6, Tier::Tier8, Tier::Tier10]
+            .into_iter()
+            .map(|tier| Want { tier: tier })
+            .collect();
+        Request::Want {
+            time: time,
+            wants: wants,
+        }
+    }
+
+    fn dont_want(tiers: &[Tier], kinds: &[TimeframeKind]) -> Request {
+        Request::DontWant {
+            tiers: tiers.iter().cloned().collect(),
+            kinds: kinds.iter().cloned().collect(),
+        }
+    }
+
+    fn invalid(command: Command, reason: &str, position: usize) -> Request {
+        Request::Invalid {
+            command: command,
+            reason: reason.to_owned(),
+            position: position,
+        }
+    }
+
+    #[test]
+    fn simple_commands() {
+        assert_eq!(Request::None, parse_message("", now(), Tz::UTC, &en()));
+        assert_eq!(Request::None, parse_message(" \t ", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Help(None), parse_message("help", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Status, parse_message("Status", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[], &[]), parse_message("don't want", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Unknown, parse_message("wnat 10", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(Request::Help(None), parse_message("?", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Status, parse_message("st", now(), Tz::UTC, &en()));
+        assert_eq!(Request::ShowMe, parse_message("whoami", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(Timeframe::Always), parse_message("w always", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Unknown, parse_message("hilfe", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn german() {
+        assert_eq!(Request::Help(None), parse_message("Hilfe", now(), Tz::UTC, &de()));
+        assert_eq!(want_all(Timeframe::Always),
+                   parse_message("will always", now(), Tz::UTC, &de()));
+        assert_eq!(dont_want(&[], &[]), parse_message("will nicht", now(), Tz::UTC, &de()));
+        assert_eq!(dont_want(&[], &[]), parse_message("nicht will", now(), Tz::UTC, &de()));
+        // English keeps working.
+        assert_eq!(dont_want(&[], &[]), parse_message("dont want", now(), Tz::UTC, &de()));
+        assert_eq!(invalid(Command::DontWant, "Expected \"will\" after \"nicht\".", 5),
+                   parse_message("nicht", now(), Tz::UTC, &de()));
+        assert_eq!(dont_want(&[Tier::Tier10], &[]),
+                   parse_message("will nicht 10", now(), Tz::UTC, &de()));
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(Request::Help(Some(Command::Want)),
+                   parse_message("help want", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Help(Some(Command::Want)),
+                   parse_message("? w", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Help(Some(Command::DontWant)),
+                   parse_message("help don't want", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Help(Some(Command::DontWant)),
+                   parse_message("hilfe will nicht", now(), Tz::UTC, &de()));
+        assert_eq!(invalid(Command::Help,
+                           "Unknown command \"foo\", expected one of help, want, dont want, \
+                            status, me, schedules, timezone, prefix, language.",
+                           5),
+                   parse_message("help foo", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Help, "Unexpected \"10\" after the command.", 10),
+                   parse_message("help want 10", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn help_tables() {
+        let is_want = |content: &str| match parse_message(content, now(), Tz::UTC, &en()) {
+            Request::Want { .. } => true,
+            _ => false,
+        };
+        for &(examples, _) in TIER_FORMS.iter() {
+            for example in examples {
+                assert!(is_want(&format!("want {}", example)), "{}", example);
+            }
+        }
+        for &(_, examples) in VALUE_FORMATS.iter() {
+            for example in examples {
+                let contents = [format!("want until {}", example),
+                                format!("want for {}", example),
+                                format!("want every {} 19:00-22:00", example)];
+                assert!(contents.iter().any(|content| is_want(content)), "{}", example);
+            }
+        }
+        // Every alternative of every timeframe, with the optional words and the values filled in.
+        for spec in TIMEFRAMES.iter() {
+            for alternative in spec.syntax.split(" | ") {
+                let mut content = alternative.replace("[", "").replace("]", "");
+                for &(value, examples) in VALUE_FORMATS.iter() {
+                    for example in examples {
+                        content = content.replacen(value, example, 1);
+                    }
+                }
+                for keyword in spec.keywords {
+                    let content = content.replacen(spec.keywords[0], keyword, 1);
+                    assert!(is_want(&format!("want {}", content)), "{}", content);
+                }
+            }
+        }
+        for &(words, kinds, _) in TIMEFRAME_KINDS.iter() {
+            for word in words {
+                assert_eq!(dont_want(&[], kinds),
+                           parse_message(&format!("dont want {}", word), now(), Tz::UTC, &en()));
+            }
+        }
+    }
+
+    #[test]
+    fn language() {
+        assert_eq!(Request::ShowLanguage, parse_message("language", now(), Tz::UTC, &en()));
+        assert_eq!(Request::SetLanguage(Language::German),
+                   parse_message("sprache deutsch", now(), Tz::UTC, &de()));
+        assert_eq!(invalid(Command::Language,
+                           "Unknown language \"fr\", expected one of English, Deutsch.",
+                           9),
+                   parse_message("language fr", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Language, "Unexpected \"x\" after the language.", 12),
+                   parse_message("lang german x", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn want() {
+        let wants = vec![Want { tier: Tier::Tier8 }, Want { tier: Tier::Tier10 }]
+            .into_iter()
+            .collect::<HashSet<Want>>();
+        assert_eq!(Request::Want {
+                       time: Timeframe::Always,
+                       wants: wants,
+                   },
+                   parse_message("want 8 10 always", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn dont_want_selectively() {
+        assert_eq!(dont_want(&[Tier::Tier10], &[]),
+                   parse_message("dont want 10", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[], &[TimeframeKind::Timespan]),
+                   parse_message("dont want for", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[Tier::Tier6, Tier::Tier8],
+                             &[TimeframeKind::UntilLogout, TimeframeKind::Timespan]),
+                   parse_message("dont want 6 until 8", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[], &[TimeframeKind::Always, TimeframeKind::Weekly]),
+                   parse_message("dont want always every", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[Tier::Tier10], &[TimeframeKind::Weekly]),
+                   parse_message("dont want 10 Every", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[], &[TimeframeKind::Always, TimeframeKind::Window]),
+                   parse_message("dont want ALWAYS From", now(), Tz::UTC, &en()));
+        assert_eq!(dont_want(&[Tier::Tier8, Tier::Tier10], &[]),
+                   parse_message("dont want 8+", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::DontWant,
+                           "Unknown tier \"7\", expected 6, 8 or 10 (or e.g. 8+, 6-10, t10, VIII \
+                            or all).",
+                           13),
+                   parse_message("dont want 10 7", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::DontWant,
+                           "Expected a tier (6, 8 or 10) or a timeframe (always, until, from, \
+                            every or for), got \"later\".",
+                           10),
+                   parse_message("dont want later", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn command_and_arguments() {
+        let parsed = parse_command("prefix", now(), Tz::UTC, &en());
+        assert_eq!(Some(Command::Prefix), parsed.spec.map(|spec| spec.command));
+        assert!(!parsed.has_arguments);
+        let parsed = parse_command("prefix !sh", now(), Tz::UTC, &en());
+        assert!(parsed.has_arguments);
+        let parsed = parse_command("Will  Nicht 10", now(), Tz::UTC, &de());
+        assert_eq!(Some(Command::DontWant), parsed.spec.map(|spec| spec.command));
+        assert!(parsed.has_arguments);
+        let parsed = parse_command("dont", now(), Tz::UTC, &en());
+        assert_eq!(Some(Command::DontWant), parsed.spec.map(|spec| spec.command));
+        let parsed = parse_command("wnat", now(), Tz::UTC, &en());
+        assert!(parsed.spec.is_none());
+        assert_eq!(Request::Unknown, parsed.request);
+    }
+
+    #[test]
+    fn tier_shorthand() {
+        let want = |tiers: &[Tier]| {
+            Request::Want {
+                time: Timeframe::UntilLogout,
+                wants: tiers.iter().map(|&tier| Want { tier: tier }).collect(),
+            }
+        };
+        let parse = |content| parse_message(content, now(), Tz::UTC, &en());
+        assert_eq!(want(&[Tier::Tier6, Tier::Tier8, Tier::Tier10]), parse("want 6-10"));
+        assert_eq!(want(&[Tier::Tier8, Tier::Tier10]), parse("want 8-10"));
+        assert_eq!(want(&[Tier::Tier8, Tier::Tier10]), parse("want 8+"));
+        assert_eq!(want(&[Tier::Tier10]), parse("want T10"));
+        assert_eq!(want(&[Tier::Tier6, Tier::Tier8]), parse("want tier6 tier8"));
+        assert_eq!(want(&[Tier::Tier6, Tier::Tier10]), parse("want VI x"));
+        assert_eq!(want(&[Tier::Tier8, Tier::Tier10]), parse("want viii-X"));
+        assert_eq!(want(&[Tier::Tier6, Tier::Tier8, Tier::Tier10]), parse("want all"));
+        assert_eq!(want_all(Timeframe::Always), parse("want ALL always"));
+    }
+
+    #[test]
+    fn unknown_tiers() {
+        let unknown = |tier: &str, position| {
+            invalid(Command::Want,
+                    &format!("Unknown tier \"{}\", expected 6, 8 or 10 (or e.g. 8+, 6-10, t10, \
+                              VIII or all).",
+                             tier),
+                    position)
+        };
+        let parse = |content| parse_message(content, now(), Tz::UTC, &en());
+        assert_eq!(unknown("7", 5), parse("want 7 always"));
+        assert_eq!(unknown("6-9", 7), parse("want 8 6-9"));
+        assert_eq!(unknown("t7", 5), parse("want t7"));
+        assert_eq!(unknown("tier12", 5), parse("want Tier12"));
+        assert_eq!(unknown("vii", 5), parse("want VII"));
+        assert_eq!(unknown("9+", 5), parse("want 9+"));
+        assert_eq!(invalid(Command::Want,
+                           "The tier range \"10-6\" is empty, the lower tier has to come first.",
+                           5),
+                   parse("want 10-6"));
+    }
+
+    #[test]
+    fn dont_without_want() {
+        assert_eq!(invalid(Command::DontWant, "Expected \"want\" after \"dont\".", 4),
+                   parse_message("dont", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::DontWant,
+                           "Expected \"want\" after \"dont\", got \"play\".",
+                           5),
+                   parse_message("dont play", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn for_duration() {
+        assert_eq!(want_all(until(1475000000 + 90 * 60)),
+                   parse_message("want for 1h30m", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn for_too_long_to_show() {
+        // Fine for time::Tm, but it's beyond the year 262143 and chrono can't show it.
+        assert_eq!(invalid(Command::Want, "Given duration is too large.", 9),
+                   parse_message("want for 2562047788015h", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn until_logout() {
+        assert_eq!(want_all(Timeframe::UntilLogout),
+                   parse_message("want until logout", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn until_time_today() {
+        // 2016-09-27 22:30 UTC
+        assert_eq!(want_all(until(1475015400)),
+                   parse_message("want until 22:30", now(), Tz::UTC, &en()));
+        // 2016-09-27 18:14 UTC
+        assert_eq!(want_all(until(1474934400 + 18 * 3600 + 14 * 60)),
+                   parse_message("want until 18:14", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn until_time_rolls_over() {
+        // 2016-09-28 10:00 UTC
+        assert_eq!(want_all(until(1475056800)),
+                   parse_message("want until 10:00", now(), Tz::UTC, &en()));
+        // 2016-09-28 18:13 UTC
+        assert_eq!(want_all(until(1475086380)),
+                   parse_message("want until 18:13", now(), Tz::UTC, &en()));
+        // 2016-09-28 00:00 UTC
+        assert_eq!(want_all(until(1475020800)),
+                   parse_message("want until 0:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn until_tomorrow() {
+        // 2016-09-28 01:00 UTC
+        assert_eq!(want_all(until(1475024400)),
+                   parse_message("want until tomorrow 01:00", now(), Tz::UTC, &en()));
+        // 2016-09-28 20:00 UTC
+        assert_eq!(want_all(until(1475092800)),
+                   parse_message("want until tomorrow 20:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn until_in_timezone() {
+        // 2016-09-27 20:30 UTC
+        assert_eq!(want_all(until(1475008200)),
+                   parse_message("want until 22:30", now(), Tz::Europe__Berlin, &en()));
+        // It's already 2016-09-28 in Tokyo, 2016-09-28 22:30 JST is 13:30 UTC.
+        assert_eq!(want_all(until(1475069400)),
+                   parse_message("want until 22:30", now(), Tz::Asia__Tokyo, &en()));
+        // It's 14:13 in New York, 2016-09-28 01:00 EDT is 05:00 UTC.
+        assert_eq!(want_all(until(1475038800)),
+                   parse_message("want until 01:00", now(), Tz::America__New_York, &en()));
+        assert_eq!(want_all(until(1475038800)),
+                   parse_message("want until tomorrow 01:00", now(), Tz::America__New_York, &en()));
+    }
+
+    #[test]
+    fn until_nonexistent_time() {
+        // 2016-03-26 12:00 UTC, the day before the switch to daylight saving time.
+        let now = time::at_utc(time::Timespec::new(1458993600, 0));
+        assert_eq!(invalid(Command::Want,
+                           "2:30 doesn't exist in timezone Europe/Berlin on that day.",
+                           20),
+                   parse_message("want until tomorrow 2:30", now, Tz::Europe__Berlin, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "2:30 doesn't exist in timezone Europe/Berlin on that day.",
+                           11),
+                   parse_message("want until 2:30", now, Tz::Europe__Berlin, &en()));
+    }
+
+    fn window(from: i64, until: i64) -> Timeframe {
+        Timeframe::Window {
+            from: time::at_utc(time::Timespec::new(from, 0)),
+            until: time::at_utc(time::Timespec::new(until, 0)),
+        }
+    }
+
+    #[test]
+    fn from_to() {
+        // 2016-09-27 20:00 to 23:00 UTC
+        assert_eq!(Request::Want {
+                       time: window(1475006400, 1475017200),
+                       wants: vec![Want { tier: Tier::Tier8 }].into_iter().collect(),
+                   },
+                   parse_message("want 8 from 20:00 to 23:00", now(), Tz::UTC, &en()));
+        // Already started, 2016-09-27 17:00 to 19:00 UTC
+        assert_eq!(want_all(window(1474995600, 1475002800)),
+                   parse_message("want from 17:00 until 19:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn timeframe_words_ignore_case() {
+        let pairs = [("want 10 Until Logout", "want 10 until logout"),
+                     ("want UNTIL Tomorrow 01:00", "want until tomorrow 01:00"),
+                     ("want Till 22:30", "want till 22:30"),
+                     ("want 8 From 20:00 To 23:00", "want 8 from 20:00 to 23:00"),
+                     ("want from TOMORROW 17:00 Until 19:00",
+                      "want from tomorrow 17:00 until 19:00"),
+                     ("want Always", "want always"),
+                     ("want For 2h", "want for 2h"),
+                     ("want After 8pm", "want after 8pm"),
+                     ("want Every Mon 19:00-22:00", "want every mon 19:00-22:00"),
+                     ("want For The Next Hour", "want for the next hour")];
+        for &(mixed, lower) in pairs.iter() {
+            let parsed = parse_message(lower, now(), Tz::UTC, &en());
+            assert!(match parsed {
+                        Request::Want { .. } => true,
+                        _ => false,
+                    },
+                    "{}",
+                    lower);
+            assert_eq!(parsed, parse_message(mixed, now(), Tz::UTC, &en()), "{}", mixed);
+        }
+    }
+
+    #[test]
+    fn from_to_past_midnight() {
+        // 2016-09-27 22:00 to 2016-09-28 02:00 UTC
+        assert_eq!(want_all(window(1475013600, 1475028000)),
+                   parse_message("want from 22:00 to 2:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn from_to_rolls_over() {
+        // 2016-09-28 09:00 to 17:00 UTC
+        assert_eq!(want_all(window(1475053200, 1475082000)),
+                   parse_message("want from 9:00 to 17:00", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(window(1475053200, 1475082000)),
+                   parse_message("want from tomorrow 9:00 to 17:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn from_to_in_timezone() {
+        // 2016-09-27 20:00 to 23:00 CEST
+        assert_eq!(want_all(window(1474999200, 1475010000)),
+                   parse_message("want from 20:00 to 23:00", now(), Tz::Europe__Berlin, &en()));
+    }
+
+    #[test]
+    fn from_without_valid_window() {
+        assert_eq!(invalid(Command::Want,
+                           "Expected a time (e.g. 20:00) or \"tomorrow\" and a time after \
+                            \"from\".",
+                           9),
+                   parse_message("want from", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "\"20\" is not a valid time, expected e.g. 22:30.",
+                           10),
+                   parse_message("want from 20 to 23", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "Expected \"to\" and an end time after the start time.",
+                           15),
+                   parse_message("want from 20:00", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "Expected \"to\" and an end time after the start time, got \"then\".",
+                           16),
+                   parse_message("want from 20:00 then 23:00", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Expected an end time (e.g. 23:00).", 18),
+                   parse_message("want from 20:00 to", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Unexpected \"x\" after the timeframe.", 25),
+                   parse_message("want from 20:00 to 23:00 x", now(), Tz::UTC, &en()));
+    }
+
+    fn every(days: u8, from: u32, until: u32) -> Timeframe {
+        Timeframe::Weekly {
+            days: days,
+            from: from,
+            until: until,
+        }
+    }
+
+    #[test]
+    fn every_days() {
+        assert_eq!(Request::Want {
+                       time: every(0b0010101, 19 * 60, 22 * 60),
+                       wants: vec![Want { tier: Tier::Tier10 }].into_iter().collect(),
+                   },
+                   parse_message("want 10 every mon,wed,fri 19:00-22:00", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(every(0b0011111, 22 * 60 + 30, 60)),
+                   parse_message("want every Mon-Fri 22:30-1:00", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(every(0b1110001, 20 * 60, 21 * 60)),
+                   parse_message("want every fri-mon 20:00-21:00", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(every(0b1100100, 20 * 60, 21 * 60)),
+                   parse_message("want every weekends,wednesday 20:00-21:00",
+                                 now(),
+                                 Tz::UTC,
+                                 &en()));
+        assert_eq!(want_all(every(0b1111111, 20 * 60, 21 * 60)),
+                   parse_message("want every day 20:00-21:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn every_without_valid_schedule() {
+        assert_eq!(invalid(Command::Want,
+                           "Expected days of the week (e.g. mon,wed,fri or mon-fri) after \
+                            \"every\".",
+                           10),
+                   parse_message("want every", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "\"funday\" is not a day of the week, expected e.g. mon,wed,fri or \
+                            mon-fri.",
+                           11),
+                   parse_message("want every mon,funday 19:00-22:00", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "Expected a time range (e.g. 19:00-22:00) after the days.",
+                           14),
+                   parse_message("want every mon", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "Expected a time range (e.g. 19:00-22:00), got \"19:00\".",
+                           15),
+                   parse_message("want every mon 19:00", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "\"25:00\" is not a valid time, expected e.g. 22:30.",
+                           15),
+                   parse_message("want every mon 19:00-25:00", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "The start and end time must differ.", 15),
+                   parse_message("want every mon 19:00-19:00", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn schedules() {
+        assert_eq!(Request::ListSchedules, parse_message("schedules", now(), Tz::UTC, &en()));
+        assert_eq!(Request::RemoveSchedule(2),
+                   parse_message("schedules remove 2", now(), Tz::UTC, &en()));
+        assert_eq!(Request::RemoveSchedule(1),
+                   parse_message("schedule Delete 1", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Schedules,
+                           "Expected the number of the schedule to remove.",
+                           16),
+                   parse_message("schedules remove", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Schedules,
+                           "Expected the number of a schedule, got \"0\".",
+                           17),
+                   parse_message("schedules remove 0", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Schedules, "Expected \"remove\", got \"add\".", 10),
+                   parse_message("schedules add", now(), Tz::UTC, &en()));
+        assert_eq!(Request::RemoveSchedule(2),
+                   parse_message("zeitpläne entfernen 2", now(), Tz::UTC, &de()));
+        assert_eq!(Request::RemoveSchedule(2),
+                   parse_message("zeitpläne remove 2", now(), Tz::UTC, &de()));
+        assert_eq!(invalid(Command::Schedules, "Expected \"entfernen\", got \"add\".", 11),
+                   parse_message("zeitpläne add", now(), Tz::UTC, &de()));
+        assert_eq!(Request::Unknown, parse_message("remove 2", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Schedules,
+                           "Unexpected \"3\" after the schedule number.",
+                           19),
+                   parse_message("schedules remove 2 3", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn prefix() {
+        assert_eq!(Request::ShowPrefix, parse_message("prefix", now(), Tz::UTC, &en()));
+        assert_eq!(Request::SetPrefix("!sh".to_owned()),
+                   parse_message("Prefix !sh", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Prefix, "The prefix can be at most 10 characters long.", 7),
+                   parse_message("prefix 12345678901", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Prefix,
+                           "The prefix can't be a mention, mentioning me always works anyway.",
+                           7),
+                   parse_message("prefix <@123>", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Prefix, "Unexpected \"sh\" after the prefix.", 9),
+                   parse_message("prefix ! sh", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Prefix, "The prefix can't contain spaces.", 7),
+                   parse_message("prefix \"! sh\"", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Prefix, "The prefix can't be empty.", 7),
+                   parse_message("prefix \"\"", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(Request::SetPrefix("!sh".to_owned()),
+                   parse_message("prefix \"!sh\"", now(), Tz::UTC, &en()));
+        assert_eq!(Request::SetPrefix("\"sh\\".to_owned()),
+                   parse_message(r#"prefix \"sh\\"#, now(), Tz::UTC, &en()));
+        assert_eq!(want_all(Timeframe::Always),
+                   parse_message("\"want\" \"always\"", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Missing closing quote.", 5),
+                   parse_message("want \"10 always", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::DontWant, "Missing closing quote.", 5),
+                   parse_message("dont \"want", now(), Tz::UTC, &en()));
+        assert_eq!(Request::Unknown, parse_message("\"want", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn timezone() {
+        assert_eq!(Request::ShowTimezone, parse_message("timezone", now(), Tz::UTC, &en()));
+        assert_eq!(Request::SetTimezone(Tz::Europe__Berlin),
+                   parse_message("timezone europe/berlin", now(), Tz::UTC, &en()));
+        assert_eq!(Request::SetTimezone(Tz::America__New_York),
+                   parse_message("tz America/New_York", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Timezone,
+                           "Unknown timezone \"CEST\". Use a name from the tz database, e.g. \
+                            Europe/Berlin or America/New_York.",
+                           9),
+                   parse_message("timezone CEST", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Timezone, "Unexpected \"x\" after the timezone.", 13),
+                   parse_message("timezone UTC x", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn until_without_valid_time() {
+        let expected = "Expected \"logout\", a time (e.g. 22:30) or \"tomorrow\" and a time after \
+                        \"until\"";
+        assert_eq!(invalid(Command::Want, &format!("{}.", expected), 13),
+                   parse_message("want 10 until", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, &format!("{}, got \"later\".", expected), 14),
+                   parse_message("want 10 until later", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Expected a time after \"tomorrow\".", 19),
+                   parse_message("want until tomorrow", now(), Tz::UTC, &en()));
+        let not_a_time = |s: &str| format!("\"{}\" is not a valid time, expected e.g. 22:30.", s);
+        for time_str in &["24:00", "12:60", "1:5", "123:00", "ab:cd", "12:", ":30", "-1:30"] {
+            assert_eq!(invalid(Command::Want, &not_a_time(time_str), 11),
+                       parse_message(&format!("want until {}", time_str), now(), Tz::UTC, &en()));
+        }
+        assert_eq!(invalid(Command::Want, &not_a_time("logout"), 20),
+                   parse_message("want until tomorrow logout", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn time_phrases() {
+        for &(phrase, meaning) in TIME_PHRASES.iter() {
+            for tz in &[Tz::UTC, Tz::Europe__Berlin] {
+                let parsed = parse_message(&format!("want 10 {}", meaning), now(), *tz, &en());
+                assert!(match parsed {
+                            Request::Want { .. } => true,
+                            _ => false,
+                        },
+                        "{}",
+                        meaning);
+                assert_eq!(parsed,
+                           parse_message(&format!("want 10 {}", phrase), now(), *tz, &en()),
+                           "{}",
+                           phrase);
+                assert_eq!(parsed,
+                           parse_message(&format!("want 10 {}", phrase.to_uppercase()),
+                                         now(),
+                                         *tz,
+                                         &en()),
+                           "{}",
+                           phrase);
+            }
+        }
+        assert_eq!(invalid(Command::Want, "Unexpected \"10\" after the timeframe.", 13),
+                   parse_message("want tonight 10", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn tonight() {
+        // 2016-09-27 18:00 to 2016-09-28 00:00 UTC, already started.
+        assert_eq!(want_all(window(1474999200, 1475020800)),
+                   parse_message("want tonight", now(), Tz::UTC, &en()));
+        // 2016-09-27 18:00 to 2016-09-28 00:00 in Berlin.
+        assert_eq!(want_all(window(1474992000, 1475013600)),
+                   parse_message("want tonight", now(), Tz::Europe__Berlin, &en()));
+    }
+
+    #[test]
+    fn after() {
+        // 2016-09-27 20:00 to 2016-09-28 00:00 UTC
+        assert_eq!(want_all(window(1475006400, 1475020800)),
+                   parse_message("want after 8PM", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(window(1475006400, 1475020800)),
+                   parse_message("want after 20:00", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want,
+                           "Expected a time (e.g. 20:00 or 8pm) after \"after\".",
+                           10),
+                   parse_message("want after", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "\"13pm\" is not a valid time, expected e.g. 22:30.", 11),
+                   parse_message("want after 13pm", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn twelve_hour_clock() {
+        // 2016-09-27 22:30 UTC
+        assert_eq!(want_all(until(1475015400)),
+                   parse_message("want until 10:30pm", now(), Tz::UTC, &en()));
+        // 2016-09-28 00:00 and 12:00 UTC
+        assert_eq!(want_all(until(1475020800)),
+                   parse_message("want until 12am", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(until(1475064000)),
+                   parse_message("want till 12pm", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(until(1475064000)),
+                   parse_message("want until noon", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(every(0b0000001, 20 * 60, 22 * 60 + 30)),
+                   parse_message("want every mon 8pm-10:30pm", now(), Tz::UTC, &en()));
+        for time_str in &["0am", "13pm", "8:5pm", "pm", "8 pm"] {
+            assert!(match parse_message(&format!("want until {}", time_str),
+                                        now(),
+                                        Tz::UTC,
+                                        &en()) {
+                        Request::Invalid { .. } => true,
+                        _ => false,
+                    },
+                    "{}",
+                    time_str);
+        }
+    }
+
+    #[test]
+    fn duration_phrases() {
+        assert_eq!(want_all(until(1475000000 + 30 * 60)),
+                   parse_message("want for half an hour", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(until(1475000000 + 2 * 3600)),
+                   parse_message("want for the next couple of hours", now(), Tz::UTC, &en()));
+        assert_eq!(want_all(until(1475000000 + 3 * 60)),
+                   parse_message("want for the next 3 minutes", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Unexpected \"or\" after the timeframe.", 17),
+                   parse_message("want for an hour or so", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Expected a number, got \"the\".", 9),
+                   parse_message("want for the moon", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "No unit given for \"2\".", 9),
+                   parse_message("want for 2 days", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn for_without_valid_duration() {
+        assert_eq!(invalid(Command::Want, "Expected a duration after \"for\".", 11),
+                   parse_message("want 10 for", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Unknown unit \"x\".", 12),
+                   parse_message("want 10 for 2x", now(), Tz::UTC, &en()));
+        assert_eq!(invalid(Command::Want, "Duration doesn't end with \"h\".", 9),
+                   parse_message("want for 1:30", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn unknown_timeframe() {
+        assert_eq!(invalid(Command::Want,
+                           "Expected a tier (6, 8 or 10) or a timeframe (always, until, from, \
+                            every or for), got \"sometime\".",
+                           7),
+                   parse_message("want 6 sometime", now(), Tz::UTC, &en()));
+    }
+
+    #[test]
+    fn trailing_tokens() {
+        assert_eq!(invalid(Command::Want, "Unexpected \"10\" after the timeframe.", 12),
+                   parse_message("want always 10", now(), Tz::UTC, &en()));
+    }
+}
+
+#[cfg(test)]
+mod tests_properties {
+    use super::{parse_command, parse_duration, suggest, PARAMETER_WORDS};
+    use common::tests_properties::{rng, random_text, ITERATIONS};
+    use message_formatter::format_request;
+    use model::{Request, UserData};
+    use replier;
+    use discord::model::OnlineStatus;
+    use vocabulary::{Vocabulary, Language};
+    use chrono_tz::Tz;
+    use rand::Rng;
+    use time;
+
+    const TIMEZONES: [Tz; 6] = [Tz::UTC,
+                                Tz::Europe__Berlin,
+                                Tz::America__New_York,
+                                Tz::Asia__Kolkata,
+                                Tz::Australia__Lord_Howe,
+                                Tz::Pacific__Chatham];
+
+    /// A random point in time between 2000 and 2100.
+    fn random_now<R: Rng>(rng: &mut R) -> time::Tm {
+        time::at_utc(time::Timespec::new(rng.gen_range(946684800i64, 4102444800), 0))
+    }
+
+    fn random_tz<R: Rng>(rng: &mut R) -> Tz {
+        *rng.choose(&TIMEZONES).unwrap()
+    }
+
+    fn random_time_of_day<R: Rng>(rng: &mut R) -> String {
+        format!("{}:{:02}", rng.gen_range(0, 24), rng.gen_range(0, 60))
+    }
+
+    fn random_duration<R: Rng>(rng: &mut R) -> String {
+        let hours = if rng.gen_weighted_bool(10) {
+            rng.gen_range(0i64, 2562047788015)
+        } else {
+            rng.gen_range(0, 100)
+        };
+        match rng.gen_range(0, 5) {
+            0 => format!("{}h{}m", hours, rng.gen_range(0, 60)),
+            1 => format!("{}.{}h", hours, rng.gen_range(0, 100000)),
+            2 => format!("{}min", rng.gen_range(0, 100000)),
+            3 => format!("{}:{:02}h", hours, rng.gen_range(0, 60)),
+            _ => format!("{}hours{}", hours, rng.gen_range(0, 60)),
+        }
+    }
+
+    /// Returns a random, mostly valid want command.
+    fn random_want<R: Rng>(rng: &mut R) -> String {
+        let tiers = ["6", "8", "10", "t8", "Tier10", "VI", "x", "6-8", "8+", "6+", "all"];
+        let days = ["mon", "Tue,thu", "mon-fri", "fri-mon", "weekends", "day", "sat,sun,wed"];
+        let mut want = "want".to_owned();
+        for _ in 0..rng.gen_range(0, 3) {
+            want.push(' ');
+            want.push_str(rng.choose(&tiers).unwrap());
+        }
+        let tomorrow = if rng.gen() { " tomorrow" } else { "" };
+        let phrases = ["tonight", "after 8pm", "till midnight", "til 10:30am", "for an hour",
+                       "for the next couple of hours", "for half an hour", "for 45 minutes"];
+        let timeframe = match rng.gen_range(0, 8) {
+            0 => "".to_owned(),
+            1 => " always".to_owned(),
+            2 => " until logout".to_owned(),
+            3 => format!(" until{} {}", tomorrow, random_time_of_day(rng)),
+            4 => {
+                format!(" from{} {} {} {}",
+                        tomorrow,
+                        random_time_of_day(rng),
+                        rng.choose(&["to", "until"]).unwrap(),
+                        random_time_of_day(rng))
+            }
+            5 => {
+                format!(" every {} {}-{}",
+                        rng.choose(&days).unwrap(),
+                        random_time_of_day(rng),
+                        random_time_of_day(rng))
+            }
+            6 => format!(" for {}", random_duration(rng)),
+            _ => format!(" {}", rng.choose(&phrases).unwrap()),
+        };
+        want + &timeframe
+    }
+
+    #[test]
+    fn no_panics() {
+        let vocabularies = [Vocabulary::new(Language::English), Vocabulary::new(Language::German)];
+        let mut words = Vocabulary::all().all_words();
+        words.extend(PARAMETER_WORDS.iter().cloned());
+        words.extend(&["6", "8+", "6-10", "VIII", "12:30", "23:59-0:00", "mon,fri", "1h30m",
+                       "1:30h", "99999999999:59h", "1.5h", ".5m", "2562047788015h"]);
+        let mut rng = rng();
+        for _ in 0..ITERATIONS {
+            let content = if rng.gen() {
+                random_text(&mut rng, &words)
+            } else {
+                random_want(&mut rng)
+            };
+            let (now, tz) = (random_now(&mut rng), random_tz(&mut rng));
+            for vocabulary in vocabularies.iter() {
+                match parse_command(&content, now, tz, vocabulary).request {
+                    Request::Invalid { position, .. } => {
+                        assert!(content.is_char_boundary(position), "{:?}", content);
+                    }
+                    Request::Want { time, wants } => {
+                        // Whatever is parsed has to be shown in the reply.
+                        let mut ud = UserData::new(OnlineStatus::Online);
+                        ud.timezone = Some(tz);
+                        ud.time_wants.insert(time, wants);
+                        replier::want(&ud, &now, Some(&content));
+                    }
+                    _ => {}
+                }
+                suggest(&content, now, tz, vocabulary);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_duration_no_panics() {
+        let mut rng = rng();
+        for _ in 0..ITERATIONS {
+            let _ = parse_duration(&random_text(&mut rng, &["h", "m", "min", "hours", ":", "."])
+                .replace(char::is_whitespace, ""));
+            let _ = parse_duration(&random_duration(&mut rng));
+        }
+    }
+
+    /// Returns a random, mostly valid command, a want one in half of the cases.
+    fn random_command<R: Rng>(rng: &mut R) -> String {
+        if rng.gen() {
+            return random_want(rng);
+        }
+        let dont_want_words = ["6", "8+", "all", "always", "until", "for", "from", "every"];
+        match rng.gen_range(0, 6) {
+            0 => {
+                let mut dont_want = "dont want".to_owned();
+                for _ in 0..rng.gen_range(0, 4) {
+                    dont_want.push(' ');
+                    dont_want.push_str(rng.choose(&dont_want_words).unwrap());
+                }
+                dont_want
+            }
+            1 => format!("timezone {}", random_tz(rng).name()),
+            2 => format!("schedules remove {}", rng.gen_range(0, 10)),
+            3 => {
+                let commands = ["prefix !sh", "prefix", "language de", "language"];
+                (*rng.choose(&commands).unwrap()).to_owned()
+            }
+            4 => {
+                let commands = ["status", "me"];
+                (*rng.choose(&commands).unwrap()).to_owned()
+            }
+            _ => {
+                let helps = ["help", "help want", "help dont want", "help status"];
+                (*rng.choose(&helps).unwrap()).to_owned()
+            }
+        }
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let vocabularies = [Vocabulary::new(Language::English), Vocabulary::new(Language::German)];
+        let mut rng = rng();
+        let mut wants_parsed = 0;
+        for _ in 0..ITERATIONS {
+            let content = random_command(&mut rng);
+            let (now, tz) = (random_now(&mut rng), random_tz(&mut rng));
+            let vocabulary = rng.choose(&vocabularies).unwrap();
+            let request = parse_command(&content, now, tz, vocabulary).request;
+            if let Request::Want { .. } = request {
+                wants_parsed += 1;
+            }
+            let formatted = match format_request(&request, &now, tz, vocabulary) {
+                Some(formatted) => formatted,
+                None => continue,
+            };
+            assert_eq!(request,
+                       parse_command(&formatted, now, tz, vocabulary).request,
+                       "{:?} formatted as {:?} at {} in {}",
+                       content,
+                       formatted,
+                       now.rfc3339(),
+                       tz.name());
+        }
+        // Make sure the property isn't trivially true.
+        assert!(wants_parsed > ITERATIONS / 4, "{}", wants_parsed);
+    }
+}
@@ -1,19 +1,62 @@
 use std::collections::{HashSet, HashMap};
 use std::error::Error;
 use time;
-use discord::model::OnlineStatus;
+use discord::model::{OnlineStatus, UserId};
 use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
+use chrono_tz::Tz;
+use timezone;
+use vocabulary::Language;
 
+#[derive(PartialEq, Debug)]
 pub enum Request {
     None,
     Unknown,
-    Help,
+    /// The message started out as a valid command, but then didn't follow its syntax.
+    Invalid {
+        command: Command,
+        reason: String,
+        /// Byte index into the message of the token that didn't fit.
+        position: usize,
+    },
+    /// Shows the help, about the given command or all of them.
+    Help(Option<Command>),
     Want {
         time: Timeframe,
         wants: HashSet<Want>,
     },
+    /// Removes the wants in the given tiers with the given kinds of timeframes. An empty set stands
+    /// for all of them.
+    DontWant {
+        tiers: HashSet<Tier>,
+        kinds: HashSet<TimeframeKind>,
+    },
+    Status,
+    /// Shows what's recorded about the user.
+    ShowMe,
+    ShowTimezone,
+    SetTimezone(Tz),
+    ListSchedules,
+    ShowPrefix,
+    /// Sets the command prefix of the guild the message was sent in.
+    SetPrefix(String),
+    ShowLanguage,
+    /// Sets the language of the command keywords in the guild the message was sent in.
+    SetLanguage(Language),
+    /// Removes the schedule with the given number, as shown when listing them (starting at 1).
+    RemoveSchedule(usize),
+}
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum Command {
+    Help,
+    Want,
     DontWant,
     Status,
+    Me,
+    Timezone,
+    Schedules,
+    Prefix,
+    Language,
 }
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
@@ -23,6 +66,20 @@ pub enum Tier {
     Tier10,
 }
 
+/// All tiers, in ascending order.
+pub const TIERS: [Tier; 3] = [Tier::Tier6, Tier::Tier8, Tier::Tier10];
+
+impl Tier {
+    /// The number the tier is known by, e.g. 10 for `Tier10`.
+    pub fn number(&self) -> u32 {
+        match *self {
+            Tier::Tier6 => 6,
+            Tier::Tier8 => 8,
+            Tier::Tier10 => 10,
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
 pub enum Timeframe {
     Always,
@@ -30,21 +87,226 @@ pub enum Timeframe {
     Timespan {
         until: time::Tm,
     },
+    /// A window in the (usually near) future. Only counts while it's active.
+    Window {
+        from: time::Tm,
+        until: time::Tm,
+    },
+    /// Recurs every week on the given days, from one time of day until another, in the user's
+    /// timezone. If `until` isn't after `from`, it ends on the next day.
+    Weekly {
+        /// Bit 0 is Monday, bit 6 is Sunday.
+        days: u8,
+        /// Minutes since midnight.
+        from: u32,
+        /// Minutes since midnight.
+        until: u32,
+    },
+}
+
+/// The variants of `Timeframe` without their data, for picking which wants to remove.
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum TimeframeKind {
+    Always,
+    UntilLogout,
+    Timespan,
+    Window,
+    Weekly,
+}
+
+/// Abbreviated names of the days of the week, starting with Monday.
+pub const WEEKDAYS: [&'static str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+impl Timeframe {
+    pub fn kind(&self) -> TimeframeKind {
+        match *self {
+            Timeframe::Always => TimeframeKind::Always,
+            Timeframe::UntilLogout => TimeframeKind::UntilLogout,
+            Timeframe::Timespan { .. } => TimeframeKind::Timespan,
+            Timeframe::Window { .. } => TimeframeKind::Window,
+            Timeframe::Weekly { .. } => TimeframeKind::Weekly,
+        }
+    }
+
+    /// Whether wants with this timeframe count at the given point in time, with weekly schedules
+    /// evaluated in the timezone. Timeframes that depend on the user being online are considered
+    /// active, that has to be checked separately.
+    pub fn is_active(&self, now: &time::Tm, tz: Tz) -> bool {
+        match *self {
+            Timeframe::Always | Timeframe::UntilLogout => true,
+            Timeframe::Timespan { until } => until > *now,
+            Timeframe::Window { from, until } => from <= *now && until > *now,
+            Timeframe::Weekly { days, from, until } => {
+                let (weekday, minutes) = match timezone::local_weekday_and_minutes(now, tz) {
+                    Some(weekday_and_minutes) => weekday_and_minutes,
+                    None => return false,
+                };
+                let on = |day: u32| days & (1 << day) != 0;
+                if from < until {
+                    on(weekday) && from <= minutes && minutes < until
+                } else {
+                    // Goes past midnight, so it may also have started yesterday.
+                    (on(weekday) && minutes >= from) || (on((weekday + 6) % 7) && minutes < until)
+                }
+            }
+        }
+    }
+
+    /// When wants with this timeframe stop counting, given that they count at the point in time
+    /// (see `is_active()`), with weekly schedules evaluated in the timezone.
+    pub fn end(&self, now: &time::Tm, tz: Tz) -> WantEnd {
+        match *self {
+            Timeframe::Always => WantEnd::Never,
+            Timeframe::UntilLogout => WantEnd::Logout,
+            Timeframe::Timespan { until } |
+            Timeframe::Window { until, .. } => WantEnd::At(until),
+            Timeframe::Weekly { until, .. } => {
+                let (minutes, date) = match (timezone::local_weekday_and_minutes(now, tz),
+                                             timezone::local_date(now, tz)) {
+                    (Some((_, minutes)), Some(date)) => (minutes, date),
+                    // Then it isn't active either.
+                    _ => return WantEnd::At(*now),
+                };
+                // Before the end time it ends today, otherwise it goes past midnight.
+                let date = if minutes < until { Some(date) } else { date.succ_opt() };
+                let end = date.and_then(|date| {
+                        timezone::from_local(date, until / 60, until % 60, tz)
+                    })
+                    .unwrap_or_else(|| {
+                        // The end time is skipped that day (or the day is out of range), the
+                        // minutes until it are close enough.
+                        let left = (until + 24 * 60 - minutes) % (24 * 60);
+                        *now + time::Duration::minutes(left as i64)
+                    });
+                WantEnd::At(end)
+            }
+        }
+    }
+
+    /// Whether wants with this timeframe will never count again after the given point in time.
+    pub fn is_expired(&self, now: &time::Tm) -> bool {
+        match *self {
+            Timeframe::Always | Timeframe::UntilLogout | Timeframe::Weekly { .. } => false,
+            Timeframe::Timespan { until } |
+            Timeframe::Window { until, .. } => until <= *now,
+        }
+    }
 }
 
 pub struct StatusReport {
+    /// Number of players who are up for any tier.
     pub num_wanting_total: usize,
-    pub num_wanting_t6: usize,
-    pub num_wanting_t8: usize,
-    pub num_wanting_t10: usize,
+    /// Each tier, in the order of `TIERS`, with the players who are up for it. The players whose
+    /// wants end first come first.
+    pub tiers: Vec<(Tier, Vec<Player>)>,
+}
+
+/// A player who is up for a tier, as listed in the status.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Player {
+    pub user_id: UserId,
+    /// The player's name, if it's known.
+    pub name: Option<String>,
+    /// Online or idle, offline players aren't up for anything.
+    pub status: OnlineStatus,
+    /// When the last of the player's wants in the tier ends.
+    pub until: WantEnd,
+}
+
+/// When wants stop counting. Ordered by which comes first.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
+pub enum WantEnd {
+    At(time::Tm),
+    /// When the player logs out.
+    Logout,
+    /// Not until the player takes them back.
+    Never,
+}
+
+/// A reply to a request. It's structured so it can be rendered as a rich embed, but also has to
+/// make sense when rendered as plain text.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Reply {
+    pub title: Option<String>,
+    pub text: String,
+    /// Pairs of field names and values.
+    pub fields: Vec<(String, String)>,
+    pub footer: Option<String>,
+    pub timestamp: Option<time::Tm>,
+    /// Color in 0xRRGGBB form.
+    pub colour: Option<u32>,
+}
+
+impl Reply {
+    /// Creates a reply consisting of nothing but the text.
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Reply {
+            title: None,
+            text: text.into(),
+            fields: Vec::new(),
+            footer: None,
+            timestamp: None,
+            colour: None,
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct UserData {
     pub status: OnlineStatus,
     pub time_wants: HashMap<Timeframe, HashSet<Want>>,
+    /// The timezone times are parsed and shown in. UTC if it isn't set.
+    pub timezone: Option<Tz>,
+    /// The user's name when they were last seen, if they have been.
+    pub name: Option<String>,
+}
+
+impl UserData {
+    pub fn new(status: OnlineStatus) -> Self {
+        UserData {
+            status: status,
+            time_wants: HashMap::new(),
+            timezone: None,
+            name: None,
+        }
+    }
+
+    pub fn timezone_or_utc(&self) -> Tz {
+        self.timezone.unwrap_or(Tz::UTC)
+    }
+
+    /// Returns the weekly schedules with their wants, in the order they're numbered in when
+    /// listing them: by their first day, then by their start time.
+    pub fn schedules(&self) -> Vec<(&Timeframe, &HashSet<Want>)> {
+        let mut schedules = self.time_wants
+            .iter()
+            .filter(|&(t, _)| t.kind() == TimeframeKind::Weekly)
+            .collect::<Vec<_>>();
+        schedules.sort_by_key(|&(t, _)| match *t {
+            Timeframe::Weekly { days, from, until } => (days.trailing_zeros(), from, until, days),
+            _ => (0, 0, 0, 0),
+        });
+        schedules
+    }
 }
 
+/// Settings of a guild (server).
+#[derive(PartialEq, Clone, Debug)]
+pub struct GuildData {
+    /// The prefix messages in public channels have to start with. The default one if it isn't set.
+    pub prefix: Option<String>,
+    /// The language of the command keywords. English if it isn't set.
+    pub language: Option<Language>,
+}
+
+impl GuildData {
+    pub fn new() -> Self {
+        GuildData {
+            prefix: None,
+            language: None,
+        }
+    }
+}
 
 #[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub struct Want {
@@ -53,7 +315,7 @@ pub struct Want {
 
 impl Encodable for UserData {
     fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
-        s.emit_seq(2, |s| {
+        s.emit_seq(4, |s| {
             try!(s.emit_seq_elt(0, |s| {
                 // Encode status.
                 s.emit_enum("OnlineStatus", |s| {
@@ -64,7 +326,7 @@ impl Encodable for UserData {
                     }
                 })
             }));
-            s.emit_seq_elt(1, |s| {
+            try!(s.emit_seq_elt(1, |s| {
                 // Encode map from timeframes to sets of wants.
                 s.emit_map(self.time_wants.len(), |s| {
                     for (i, (k, ref v)) in self.time_wants.iter().enumerate() {
@@ -81,14 +343,24 @@ impl Encodable for UserData {
                     }
                     Ok(())
                 })
-            })
+            }));
+            try!(s.emit_seq_elt(2, |s| {
+                // Encode the timezone by its name.
+                s.emit_option(|s| {
+                    match self.timezone {
+                        Some(tz) => s.emit_option_some(|s| s.emit_str(tz.name())),
+                        None => s.emit_option_none(),
+                    }
+                })
+            }));
+            s.emit_seq_elt(3, |s| self.name.encode(s))
         })
     }
 }
 
 impl Decodable for UserData {
     fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
-        d.read_seq(|d, _| {
+        d.read_seq(|d, len| {
             let status = try!(d.read_seq_elt(0, |d| {
                 d.read_enum("OnlineStatus", |d| {
                     d.read_enum_variant(&["Offline", "Online", "Idle"], |_, i| {
@@ -120,9 +392,65 @@ impl Decodable for UserData {
                     Ok(time_wants)
                 })
             }));
+            // User data saved before timezones existed only has two elements.
+            let timezone = if len > 2 {
+                try!(d.read_seq_elt(2, |d| {
+                    d.read_option(|d, is_some| {
+                        if is_some {
+                            let name = try!(d.read_str());
+                            timezone::parse_timezone(&name).map(Some).map_err(|e| d.error(&e))
+                        } else {
+                            Ok(None)
+                        }
+                    })
+                }))
+            } else {
+                None
+            };
+            // User data saved before names were kept only has three elements.
+            let name = if len > 3 {
+                try!(d.read_seq_elt(3, |d| Option::<String>::decode(d)))
+            } else {
+                None
+            };
             Ok(UserData {
                 status: status,
                 time_wants: time_wants,
+                timezone: timezone,
+                name: name,
+            })
+        })
+    }
+}
+
+impl Encodable for GuildData {
+    // Encoded as a sequence so more settings can be added later.
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_seq(2, |s| {
+            try!(s.emit_seq_elt(0, |s| self.prefix.encode(s)));
+            // Encode the language by its code.
+            s.emit_seq_elt(1, |s| self.language.map(|l| l.code()).encode(s))
+        })
+    }
+}
+
+impl Decodable for GuildData {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        d.read_seq(|d, len| {
+            let prefix = try!(d.read_seq_elt(0, |d| Option::<String>::decode(d)));
+            // Guild data saved before languages existed only has one element.
+            let language = if len > 1 {
+                let code = try!(d.read_seq_elt(1, |d| Option::<String>::decode(d)));
+                match code {
+                    Some(code) => Some(try!(Language::parse(&code).map_err(|e| d.error(&e)))),
+                    None => None,
+                }
+            } else {
+                None
+            };
+            Ok(GuildData {
+                prefix: prefix,
+                language: language,
             })
         })
     }
@@ -131,13 +459,25 @@ impl Decodable for UserData {
 impl Encodable for Timeframe {
     // We have to encode the timeframe as a string so we can use it as a key in a map (json...).
     // First the type of timeframe. Then, if it's a timespan, the seconds and nanoseconds of the
-    // timespec, all separated by colons.
+    // timespec, or if it's a window, the seconds and nanoseconds of its start and end, or if it's
+    // weekly, the days bitmask and the start and end in minutes, all separated by colons.
     fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
         match *self {
             Timeframe::Timespan { until } => {
                 let timespec = until.to_timespec();
                 s.emit_str(&format!("Timespan:{}:{}", timespec.sec, timespec.nsec))
             }
+            Timeframe::Window { from, until } => {
+                let (from, until) = (from.to_timespec(), until.to_timespec());
+                s.emit_str(&format!("Window:{}:{}:{}:{}",
+                                    from.sec,
+                                    from.nsec,
+                                    until.sec,
+                                    until.nsec))
+            }
+            Timeframe::Weekly { days, from, until } => {
+                s.emit_str(&format!("Weekly:{}:{}:{}", days, from, until))
+            }
             Timeframe::Always => s.emit_str("Always"),
             Timeframe::UntilLogout => s.emit_str("UntilLogout"),
         }
@@ -147,27 +487,52 @@ impl Encodable for Timeframe {
 impl Decodable for Timeframe {
     fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
         let s = try!(d.read_str());
-        let mut split = s.splitn(3, ':');
-        match split.next() {
+        let mut type_and_fields = s.splitn(2, ':');
+        let kind = type_and_fields.next();
+        let num_fields = match kind {
+            Some("Timespan") => 2,
+            Some("Window") => 4,
+            Some("Weekly") => 3,
+            _ => 0,
+        };
+        let fields = try!(split_fields(type_and_fields.next(), num_fields)
+            .map_err(|e| d.error(&e)));
+        let mut split = fields.into_iter();
+        match kind {
             Some("Timespan") => {
-                let sec = try!(if let Some(sec_str) = split.next() {
-                    sec_str.parse::<i64>().map_err(|e| {
-                        d.error(&format!("Error parsing seconds: {}.", e.description()))
-                    })
-                } else {
-                    return Err(d.error("Timespan contained no seconds."));
-                });
-                let nsec = try!(if let Some(nsec_str) = split.next() {
-                    nsec_str.parse::<i32>().map_err(|e| {
-                        d.error(&format!("Error parsing nanoseconds: {}.", e.description()))
-                    })
-                } else {
-                    return Err(d.error("Timespan contained no seconds."));
-                });
-                let timespec = time::Timespec::new(sec, nsec);
-                let tm = time::at_utc(timespec);
+                let tm = try!(decode_tm(&mut split).map_err(|e| d.error(&e)));
                 Ok(Timeframe::Timespan { until: tm })
             }
+            Some("Window") => {
+                let from = try!(decode_tm(&mut split).map_err(|e| d.error(&e)));
+                let until = try!(decode_tm(&mut split).map_err(|e| d.error(&e)));
+                Ok(Timeframe::Window {
+                    from: from,
+                    until: until,
+                })
+            }
+            Some("Weekly") => {
+                let mut next = |what: &str| -> Result<u32, String> {
+                    split.next()
+                        .ok_or_else(|| format!("Weekly timeframe contained no {}.", what))
+                        .and_then(|part| {
+                            part.parse::<u32>().map_err(|e| {
+                                format!("Error parsing {}: {}.", what, e.description())
+                            })
+                        })
+                };
+                let days = try!(next("days").map_err(|e| d.error(&e)));
+                let from = try!(next("start").map_err(|e| d.error(&e)));
+                let until = try!(next("end").map_err(|e| d.error(&e)));
+                if days == 0 || days >= 1 << 7 || from >= 24 * 60 || until >= 24 * 60 {
+                    return Err(d.error("Weekly timeframe out of range."));
+                }
+                Ok(Timeframe::Weekly {
+                    days: days as u8,
+                    from: from,
+                    until: until,
+                })
+            }
             Some("Always") => Ok(Timeframe::Always),
             Some("UntilLogout") => Ok(Timeframe::UntilLogout),
             _ => Err(d.error("Unknown timeframe type.")),
@@ -175,6 +540,42 @@ impl Decodable for Timeframe {
     }
 }
 
+/// Splits the fields after the type of an encoded timeframe, of which there have to be exactly the
+/// given number.
+fn split_fields(fields: Option<&str>, num: usize) -> Result<Vec<&str>, String> {
+    let split = match fields {
+        // Splits into one more than expected, so extra fields show.
+        Some(fields) => fields.splitn(num + 1, ':').collect::<Vec<&str>>(),
+        None => Vec::new(),
+    };
+    if split.len() != num {
+        return Err(format!("Timeframe didn't have {} fields after its type.", num));
+    }
+    Ok(split)
+}
+
+/// Decodes a point in time from the next two parts of an encoded timeframe, the seconds and
+/// nanoseconds of the timespec.
+fn decode_tm<'a, I: Iterator<Item = &'a str>>(split: &mut I) -> Result<time::Tm, String> {
+    let sec = try!(if let Some(sec_str) = split.next() {
+        sec_str.parse::<i64>()
+            .map_err(|e| format!("Error parsing seconds: {}.", e.description()))
+    } else {
+        return Err("Timeframe contained no seconds.".to_owned());
+    });
+    let nsec = try!(if let Some(nsec_str) = split.next() {
+        nsec_str.parse::<i32>()
+            .map_err(|e| format!("Error parsing nanoseconds: {}.", e.description()))
+    } else {
+        return Err("Timeframe contained no nanoseconds.".to_owned());
+    });
+    let tm = time::at_utc(time::Timespec::new(sec, nsec));
+    if !timezone::in_range(&tm) {
+        return Err("Timeframe contained a time out of range.".to_owned());
+    }
+    Ok(tm)
+}
+
 impl Encodable for Want {
     fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
         self.tier.encode(s)
@@ -210,12 +611,130 @@ impl Decodable for Tier {
     }
 }
 
+#[cfg(test)]
+mod tests_timeframe {
+    use super::{Timeframe, WantEnd};
+    use chrono_tz::Tz;
+    use time;
+
+    /// 2016-09-26 00:00 UTC, a Monday.
+    const MONDAY: i64 = 1474848000;
+    const HOUR: i64 = 3600;
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    #[test]
+    fn active_and_expired() {
+        let window = Timeframe::Window {
+            from: at(100),
+            until: at(200),
+        };
+        let timespan = Timeframe::Timespan { until: at(200) };
+        let cases = vec![(window, 99, false, false),
+                         (window, 100, true, false),
+                         (window, 199, true, false),
+                         (window, 200, false, true),
+                         (timespan, 0, true, false),
+                         (timespan, 199, true, false),
+                         (timespan, 200, false, true),
+                         (Timeframe::Always, 1000, true, false),
+                         (Timeframe::UntilLogout, 1000, true, false)];
+        for (timeframe, now, active, expired) in cases {
+            assert_eq!(active,
+                       timeframe.is_active(&at(now), Tz::UTC),
+                       "{:?} at {}",
+                       timeframe,
+                       now);
+            assert_eq!(expired, timeframe.is_expired(&at(now)), "{:?} at {}", timeframe, now);
+        }
+    }
+
+    #[test]
+    fn weekly() {
+        // Mondays from 19:00 to 22:00.
+        let evening = Timeframe::Weekly {
+            days: 1,
+            from: 19 * 60,
+            until: 22 * 60,
+        };
+        // Mondays and Wednesdays from 22:00 to 02:00.
+        let night = Timeframe::Weekly {
+            days: 1 | 1 << 2,
+            from: 22 * 60,
+            until: 2 * 60,
+        };
+        let cases = vec![(evening, MONDAY + 19 * HOUR - 1, false),
+                         (evening, MONDAY + 19 * HOUR, true),
+                         (evening, MONDAY + 22 * HOUR - 1, true),
+                         (evening, MONDAY + 22 * HOUR, false),
+                         (evening, MONDAY + 24 * HOUR + 20 * HOUR, false),
+                         (evening, MONDAY + 7 * 24 * HOUR + 20 * HOUR, true),
+                         (night, MONDAY + 22 * HOUR - 1, false),
+                         (night, MONDAY + 22 * HOUR, true),
+                         (night, MONDAY + 26 * HOUR - 1, true),
+                         (night, MONDAY + 26 * HOUR, false),
+                         (night, MONDAY + 24 * HOUR + 23 * HOUR, false),
+                         (night, MONDAY + 48 * HOUR + 23 * HOUR, true),
+                         (night, MONDAY + 72 * HOUR + HOUR, true),
+                         (night, MONDAY + 96 * HOUR + HOUR, false)];
+        for (timeframe, now, active) in cases {
+            assert_eq!(active,
+                       timeframe.is_active(&at(now), Tz::UTC),
+                       "{:?} at {}",
+                       timeframe,
+                       now);
+            assert!(!timeframe.is_expired(&at(now)));
+        }
+    }
+
+    #[test]
+    fn weekly_in_timezone() {
+        let evening = Timeframe::Weekly {
+            days: 1,
+            from: 19 * 60,
+            until: 22 * 60,
+        };
+        // 17:30 UTC is 19:30 CEST.
+        let now = at(MONDAY + 17 * HOUR + 1800);
+        assert!(evening.is_active(&now, Tz::Europe__Berlin));
+        assert!(!evening.is_active(&now, Tz::UTC));
+    }
+
+    #[test]
+    fn end() {
+        let night = Timeframe::Weekly {
+            days: 1,
+            from: 22 * 60,
+            until: 2 * 60,
+        };
+        assert_eq!(WantEnd::At(at(MONDAY + 26 * HOUR)),
+                   night.end(&at(MONDAY + 23 * HOUR), Tz::UTC));
+        assert_eq!(WantEnd::At(at(MONDAY + 26 * HOUR)),
+                   night.end(&at(MONDAY + 25 * HOUR), Tz::UTC));
+        // 02:00 in Berlin is midnight UTC in summer.
+        assert_eq!(WantEnd::At(at(MONDAY + 24 * HOUR)),
+                   night.end(&at(MONDAY + 21 * HOUR), Tz::Europe__Berlin));
+        let window = Timeframe::Window {
+            from: at(100),
+            until: at(200),
+        };
+        assert_eq!(WantEnd::At(at(200)), window.end(&at(150), Tz::UTC));
+        assert_eq!(WantEnd::Logout, Timeframe::UntilLogout.end(&at(150), Tz::UTC));
+        assert!(WantEnd::At(at(200)) < WantEnd::Logout);
+        assert!(WantEnd::Logout < WantEnd::Never);
+    }
+}
+
 #[cfg(test)]
 mod tests_serialization {
-    use super::{Tier, Want, Timeframe, UserData};
+    use super::{Tier, Want, Timeframe, UserData, GuildData};
+    use vocabulary::Language;
     use discord::model::OnlineStatus;
     use std::collections::{HashMap, HashSet};
     use rustc_serialize::json::{encode, decode};
+    use chrono_tz::Tz;
     use time;
 
     #[test]
@@ -246,7 +765,16 @@ mod tests_serialization {
         let timeframes = vec![Timeframe::Always,
                               Timeframe::UntilLogout,
                               Timeframe::Timespan { until: until1 },
-                              Timeframe::Timespan { until: until2 }];
+                              Timeframe::Timespan { until: until2 },
+                              Timeframe::Window {
+                                  from: until1,
+                                  until: until2,
+                              },
+                              Timeframe::Weekly {
+                                  days: 0b1010101,
+                                  from: 19 * 60,
+                                  until: 30,
+                              }];
         for timeframe in timeframes {
             let encoded = encode(&timeframe).unwrap();
             let decoded = decode::<Timeframe>(&encoded).unwrap();
@@ -254,6 +782,66 @@ mod tests_serialization {
         }
     }
 
+    #[test]
+    fn timeframe_invalid() {
+        let encoded = vec!["\"Timespan\"",
+                           "\"Timespan:12\"",
+                           "\"Timespan:x:0\"",
+                           "\"Window:12:0\"",
+                           "\"Window:12:0:13\"",
+                           "\"Weekly:1:1140\"",
+                           "\"Weekly:0:1140:1320\"",
+                           "\"Weekly:128:1140:1320\"",
+                           "\"Weekly:1:1440:1320\"",
+                           "\"Weekly:1:x:1320\"",
+                           "\"Sometimes\"",
+                           // Beyond the year 262143.
+                           "\"Timespan:9223372036854:0\"",
+                           "\"Window:0:0:9223372036854:0\""];
+        for e in encoded {
+            assert!(decode::<Timeframe>(e).is_err(), "encoded: {}", e);
+        }
+    }
+
+    #[test]
+    fn timeframe_extra_fields() {
+        let encoded = vec!["\"Timespan:12:0:5\"",
+                           "\"Window:12:0:13:0:\"",
+                           "\"Weekly:1:1140:1320:0\"",
+                           "\"Always:\"",
+                           "\"UntilLogout:0\""];
+        for e in encoded {
+            assert!(decode::<Timeframe>(e).is_err(), "encoded: {}", e);
+        }
+    }
+
+    #[test]
+    fn guilddata() {
+        let languages = vec![None, Some(Language::English), Some(Language::German)];
+        for prefix in vec![None, Some("!sh".to_owned()), Some("\"[]".to_owned())] {
+            for language in languages.iter() {
+                let guild_data = GuildData {
+                    prefix: prefix.clone(),
+                    language: *language,
+                };
+                let encoded = encode(&guild_data).unwrap();
+                let decoded = decode::<GuildData>(&encoded).unwrap();
+                assert_eq!(guild_data, decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn guilddata_without_language() {
+        let decoded = decode::<GuildData>("[\"!sh\"]").unwrap();
+        assert_eq!(GuildData {
+                       prefix: Some("!sh".to_owned()),
+                       language: None,
+                   },
+                   decoded);
+        assert!(decode::<GuildData>("[null,\"xx\"]").is_err());
+    }
+
     #[test]
     fn userdata() {
         let statuses = vec![OnlineStatus::Online, OnlineStatus::Offline, OnlineStatus::Idle];
@@ -267,17 +855,48 @@ mod tests_serialization {
             let until = time::at(time::Timespec::new(12345678, 2345));
             time_wants.insert(Timeframe::Always, wants1);
             time_wants.insert(Timeframe::UntilLogout, wants2);
-            time_wants.insert(Timeframe::Timespan { until: until }, wants3);
+            time_wants.insert(Timeframe::Timespan { until: until }, wants3.clone());
+            time_wants.insert(Timeframe::Window {
+                                  from: until,
+                                  until: until + time::Duration::hours(2),
+                              },
+                              wants3.clone());
+            time_wants.insert(Timeframe::Weekly {
+                                  days: 0b0010101,
+                                  from: 19 * 60,
+                                  until: 22 * 60,
+                              },
+                              wants3);
             time_wants
         };
+        let timezones = vec![None, Some(Tz::UTC), Some(Tz::Europe__Berlin)];
         for status in statuses {
-            let user_data = UserData {
-                status: status,
-                time_wants: time_wants.clone(),
-            };
-            let encoded = encode(&user_data).unwrap();
-            let decoded = decode::<UserData>(&encoded).unwrap();
-            assert_eq!(user_data, decoded);
+            for timezone in timezones.iter() {
+                let user_data = UserData {
+                    status: status,
+                    time_wants: time_wants.clone(),
+                    timezone: *timezone,
+                    name: Some("Player".to_owned()),
+                };
+                let encoded = encode(&user_data).unwrap();
+                let decoded = decode::<UserData>(&encoded).unwrap();
+                assert_eq!(user_data, decoded);
+            }
         }
     }
+
+    #[test]
+    fn userdata_without_timezone() {
+        let encoded = "[\"Idle\",{\"Always\":[6]}]";
+        let decoded = decode::<UserData>(encoded).unwrap();
+        let mut expected = UserData::new(OnlineStatus::Idle);
+        expected.time_wants.insert(Timeframe::Always,
+                                   vec![Want { tier: Tier::Tier6 }].into_iter().collect());
+        assert_eq!(expected, decoded);
+    }
+
+    #[test]
+    fn userdata_unknown_timezone() {
+        assert!(decode::<UserData>("[\"Idle\",{},\"Europe/Nowhere\"]").is_err());
+    }
 }
@@ -1,73 +1,1303 @@
-use model::{UserData, Tier, Timeframe, StatusReport};
-use std::iter;
+use model::{UserData, Timeframe, StatusReport, Player, WantEnd, Reply, Command, Want,
+            TIERS, WEEKDAYS};
+use vocabulary::{Vocabulary, Keyword, Language};
+use commands::{self, ArgumentTable, COMMANDS};
+use common;
+use message_parser::{TIER_FORMS, TIMEFRAMES, TIMEFRAME_KINDS, TIME_PHRASES, VALUE_FORMATS};
+use timezone;
+use chrono_tz::Tz;
+use discord::model::OnlineStatus;
 use std::collections::HashSet;
+use time;
 
-pub fn unknown_request(msg_content: &str) -> String {
-    "\"".to_owned() + msg_content + "\" is not a valid request. Type \"help\" to find out what is."
+/// Maximum number of characters of user input that are quoted back in a reply.
+const MAX_QUOTE_LEN: usize = 100;
+
+/// Maximum number of players listed by name per tier in the status, so the list fits in a field.
+const MAX_PLAYERS_LISTED: usize = 12;
+
+const COLOUR_INFO: u32 = 0x3498db;
+const COLOUR_SUCCESS: u32 = 0x2ecc71;
+const COLOUR_ERROR: u32 = 0xe74c3c;
+
+/// Replies to a request that doesn't start with a command. The suggestion is a corrected version
+/// of the message, if there is one.
+pub fn unknown_request(msg_content: &str,
+                       suggestion: Option<&str>,
+                       vocabulary: &Vocabulary)
+                       -> Reply {
+    let mut reply = Reply::new(format!("\"{}\" is not a valid request.{} Type \"{}\" to find \
+                                        out what is.",
+                                       sanitize(msg_content),
+                                       did_you_mean(suggestion),
+                                       vocabulary.word(Keyword::Help)));
+    reply.colour = Some(COLOUR_ERROR);
+    reply
+}
+
+/// Replies to a request that started out as a valid command but didn't follow its syntax. The
+/// position is the byte index of the token in the message that didn't fit.
+pub fn invalid_request(msg_content: &str,
+                       command: Command,
+                       reason: &str,
+                       position: usize,
+                       suggestion: Option<&str>,
+                       vocabulary: &Vocabulary)
+                       -> Reply {
+    let (offending, _) = common::str_head_tail(msg_content.get(position..).unwrap_or(""));
+    let location = if offending.is_empty() {
+        "at the end".to_owned()
+    } else {
+        format!("at \"{}\"", sanitize(&offending))
+    };
+    let mut reply = Reply::new(format!("\"{}\" is not a valid {} request. Problem {}: {}{}",
+                                       sanitize(msg_content),
+                                       command_name(command, vocabulary),
+                                       location,
+                                       sanitize(reason),
+                                       did_you_mean(suggestion)));
+    reply.fields.push(("Usage".to_owned(),
+                        format!("`{}`\nType `{} {}` for more.",
+                                usage(command, vocabulary),
+                                vocabulary.word(Keyword::Help),
+                                command_name(command, vocabulary))));
+    reply.colour = Some(COLOUR_ERROR);
+    reply
+}
+
+/// Returns " Did you mean `<suggestion>`?", or nothing if there's no suggestion.
+fn did_you_mean(suggestion: Option<&str>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(" Did you mean `{}`?", sanitize_code(suggestion)),
+        None => String::new(),
+    }
+}
+
+/// The name of the command in the vocabulary's language, e.g. "dont want".
+fn command_name(command: Command, vocabulary: &Vocabulary) -> String {
+    commands::spec(command).name(vocabulary)
+}
+
+fn usage(command: Command, vocabulary: &Vocabulary) -> String {
+    commands::spec(command).usage(vocabulary)
+}
+
+/// Makes user-supplied text safe to be quoted in a reply.
+///
+/// The text is truncated to `MAX_QUOTE_LEN` characters and put on a single line. Mentions
+/// (including @everyone and @here) are neutralized by putting a zero-width space after every "@",
+/// and markdown control characters are escaped with a backslash, so the text is displayed as-is.
+pub fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    for c in chars.by_ref().take(MAX_QUOTE_LEN) {
+        match c {
+            '@' => sanitized.push_str("@\u{200B}"),
+            '\\' | '*' | '_' | '~' | '`' | '|' => {
+                sanitized.push('\\');
+                sanitized.push(c);
+            }
+            // Only a leading ">" starts a block quote.
+            '>' if sanitized.is_empty() => sanitized.push_str("\\>"),
+            c if c.is_whitespace() => sanitized.push(' '),
+            c => sanitized.push(c),
+        }
+    }
+    if chars.next().is_some() {
+        sanitized.push_str("...");
+    }
+    sanitized
+}
+
+/// Makes user-supplied text safe to be quoted as inline code in a reply.
+///
+/// Markdown and mentions aren't interpreted in code, so only backticks, which would end it, need
+/// replacing. Like `sanitize()`, the text is truncated and put on a single line.
+fn sanitize_code(text: &str) -> String {
+    let mut sanitized = text.chars()
+        .take(MAX_QUOTE_LEN)
+        .map(|c| match c {
+            '`' => '\'',
+            c if c.is_whitespace() => ' ',
+            c => c,
+        })
+        .collect::<String>();
+    if text.chars().count() > MAX_QUOTE_LEN {
+        sanitized.push_str("...");
+    }
+    sanitized
 }
 
-pub fn help() -> String {
-    // TODO
-    "This is an unhelpful help text. There'll be a better one, I promise.".to_owned()
+/// Lists the commands with all their words in the vocabulary, their usage and a summary.
+pub fn help(vocabulary: &Vocabulary) -> Reply {
+    let help = vocabulary.word(Keyword::Help);
+    let mut reply = Reply::new(format!("Start your message with one of these commands. Parts in \
+                                        brackets are optional, | separates alternatives. Type \
+                                        `{} <command>` to find out more about one, e.g. `{} {}`.",
+                                       help,
+                                       help,
+                                       command_name(Command::Want, vocabulary)));
+    reply.title = Some("Help".to_owned());
+    for spec in COMMANDS.iter() {
+        reply.fields.push((command_names(spec.command, vocabulary),
+                           format!("`{}` {}", usage(spec.command, vocabulary), spec.summary)));
+    }
+    // The first example of each command that has arguments.
+    let examples = COMMANDS.iter()
+        .filter_map(|spec| {
+            let name = spec.name(vocabulary);
+            spec.example_commands(vocabulary)
+                .into_iter()
+                .find(|example| *example != name)
+                .map(|example| format!("`{}`", example))
+        })
+        .collect::<Vec<String>>();
+    reply.fields.push(("Examples".to_owned(), examples.join("\n")));
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+/// All words for the command in the vocabulary, e.g. "want, w".
+fn command_names(command: Command, vocabulary: &Vocabulary) -> String {
+    let keywords = commands::spec(command).keywords;
+    // Aliases can only be listed for single words.
+    if keywords.len() == 1 {
+        vocabulary.words(keywords[0]).join(", ")
+    } else {
+        command_name(command, vocabulary)
+    }
 }
 
-pub fn want(ud: &UserData) -> String {
-    // TODO sort based on tier (tier 6 should always be first etc.) and group to compactify the
-    // information
-    let mut kind = String::new();
-    // TODO use .sum() instead of .fold() once it's stable
-    let num_wants = ud.time_wants.values().map(HashSet::len).fold(0, |a, i| a + i);
-    for (i, (time, want)) in ud.time_wants
+/// Explains everything about using the command: its usage, what its arguments can be and
+/// examples of it.
+pub fn command_help(command: Command, vocabulary: &Vocabulary) -> Reply {
+    let spec = commands::spec(command);
+    let mut reply = Reply::new(spec.description);
+    reply.title = Some(format!("Help: {}", command_names(command, vocabulary)));
+    reply.fields.push(("Usage".to_owned(), format!("`{}`", usage(command, vocabulary))));
+    for &table in spec.tables {
+        reply.fields.push(argument_table(table));
+    }
+    let examples = spec.example_commands(vocabulary)
+        .iter()
+        .map(|example| format!("`{}`", example))
+        .collect::<Vec<String>>();
+    reply.fields.push(("Examples".to_owned(), examples.join("\n")));
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+/// Formats a table of what an argument can be as a field, one line per row.
+fn argument_table(table: ArgumentTable) -> (String, String) {
+    // E.g. "`until` (or till, til)".
+    let with_aliases = |syntax: &str, aliases: &[&str]| if aliases.is_empty() {
+        format!("`{}`", syntax)
+    } else {
+        format!("`{}` (or {})", syntax, aliases.join(", "))
+    };
+    let quoted = |examples: &[&str]| {
+        examples.iter().map(|example| format!("`{}`", example)).collect::<Vec<String>>()
+    };
+    let (name, rows) = match table {
+        ArgumentTable::Tiers => {
+            let mut rows = TIER_FORMS.iter()
+                .map(|&(examples, description)| {
+                    format!("{}: {}", quoted(examples).join(", "), description)
+                })
+                .collect::<Vec<String>>();
+            rows.push("Several can be given, e.g. `6 10`.".to_owned());
+            ("<tiers>", rows)
+        }
+        ArgumentTable::Timeframes => {
+            let rows = TIMEFRAMES.iter()
+                .map(|spec| {
+                    format!("{}: {}",
+                            with_aliases(spec.syntax, &spec.keywords[1..]),
+                            spec.description)
+                })
+                .collect();
+            ("<timeframe>", rows)
+        }
+        ArgumentTable::ValueFormats => {
+            let rows = VALUE_FORMATS.iter()
+                .map(|&(value, examples)| format!("`{}`: {}", value, quoted(examples).join(", ")))
+                .collect();
+            ("Values", rows)
+        }
+        ArgumentTable::TimePhrases => {
+            let rows = TIME_PHRASES.iter()
+                .map(|&(phrase, meaning)| format!("`{}` means `{}`", phrase, meaning))
+                .collect();
+            ("Phrases", rows)
+        }
+        ArgumentTable::TimeframeKinds => {
+            let rows = TIMEFRAME_KINDS.iter()
+                .map(|&(words, _, description)| {
+                    format!("{}: {}", with_aliases(words[0], &words[1..]), description)
+                })
+                .collect();
+            ("<timeframe kinds>", rows)
+        }
+    };
+    (name.to_owned(), rows.join("\n"))
+}
+
+/// Lists the tiers the user is up for grouped by timeframe, e.g. "tier 6 and 8 until you log
+/// out, tier 10 whenever you're online". The timeframes are ordered by when they expire (see
+/// `expiry_order()`), those that already have are left out. Returns an empty string if there are
+/// none.
+fn format_wants(ud: &UserData, now: &time::Tm) -> String {
+    let mut timeframes = ud.time_wants
+        .iter()
+        .filter(|&(time, wants)| !time.is_expired(now) && !wants.is_empty())
+        .collect::<Vec<_>>();
+    timeframes.sort_by_key(|&(time, _)| expiry_order(time));
+    timeframes.iter()
+        .map(|&(time, wants)| {
+            format!("{} {}",
+                    format_tiers(wants),
+                    describe_timeframe(time, now, ud.timezone_or_utc()))
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Joins the items with commas and an "and" before the last one, e.g. "6, 8 and 10".
+fn join_with_and(items: &[String]) -> String {
+    match items.split_last() {
+        None => String::new(),
+        Some((last, rest)) if rest.is_empty() => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// Describes a timeframe the way it's used in sentences, e.g. "until 22:30 UTC", with times in the
+/// timezone relative to now.
+fn describe_timeframe(time: &Timeframe, now: &time::Tm, tz: Tz) -> String {
+    match *time {
+        Timeframe::Always => "whenever you're online".to_owned(),
+        Timeframe::UntilLogout => "until you log out".to_owned(),
+        Timeframe::Timespan { until } => format!("until {}", format_time(&until, now, tz)),
+        Timeframe::Window { from, until } => {
+            format!("from {} until {}",
+                    format_time(&from, now, tz),
+                    format_time(&until, now, tz))
+        }
+        Timeframe::Weekly { days, from, until } => format_schedule(days, from, until, tz),
+    }
+}
+
+/// Orders timeframes by when they expire: until logout (the current session) first, then the
+/// timed ones by their end, weekly schedules by their first day and start time, and always.
+fn expiry_order(time: &Timeframe) -> (u8, time::Timespec, time::Timespec, u32, u32, u8) {
+    let never = time::Timespec::new(0, 0);
+    match *time {
+        Timeframe::UntilLogout => (0, never, never, 0, 0, 0),
+        Timeframe::Timespan { until } => (1, until.to_timespec(), never, 0, 0, 0),
+        Timeframe::Window { from, until } => {
+            (1, until.to_timespec(), from.to_timespec(), 0, 0, 0)
+        }
+        Timeframe::Weekly { days, from, until } => {
+            (2, never, never, days.trailing_zeros() * 24 * 60 + from, until, days)
+        }
+        Timeframe::Always => (3, never, never, 0, 0, 0),
+    }
+}
+
+/// Formats the time left until something, rounded up to whole minutes, e.g. "1h 5m".
+fn format_remaining(duration: time::Duration) -> String {
+    let minutes = ::std::cmp::max((duration.num_seconds() + 59) / 60, 0);
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes % (24 * 60) / 60, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Shows what's noted about the user: the tiers they're up for and until when, the online status
+/// they're tracked with and their timezone, with the commands to change them.
+pub fn me(ud: Option<&UserData>, now: &time::Tm, vocabulary: &Vocabulary) -> Reply {
+    let mut reply = Reply::new("");
+    reply.title = Some("What I've noted about you".to_owned());
+    reply.colour = Some(COLOUR_INFO);
+    let ud = match ud {
+        Some(ud) => ud,
+        None => {
+            reply.text = format!("Nothing yet. Type `{}` to note you're up for Stronghold.",
+                                 command_name(Command::Want, vocabulary));
+            return reply;
+        }
+    };
+    let status = match ud.status {
+        OnlineStatus::Online => "I see you as online.",
+        OnlineStatus::Idle => "I see you as idle, your wants still count.",
+        OnlineStatus::Offline => "I see you as offline, so your wants only count once you're back.",
+    };
+    reply.text = status.to_owned();
+    let tz = ud.timezone_or_utc();
+    // Wants that have expired are only removed once the status is requested.
+    let mut timeframes = ud.time_wants
         .iter()
-        .flat_map(|(t, ws)| iter::repeat(t).zip(ws.iter()))
-        .enumerate() {
-        match want.tier {
-            Tier::Tier6 => kind.push_str("tier 6 "),
-            Tier::Tier8 => kind.push_str("tier 8 "),
-            Tier::Tier10 => kind.push_str("tier 10 "),
-        }
-        if i == 0 {
-            // First in the list, add a Stronghold.
-            kind.push_str(" Stronghold ");
-        }
-        // TODO is there a way i don't have to use * here? in constructing the iterator in the for?
-        match *time {
-            Timeframe::Always => kind.push_str("whenever you're online"),
-            Timeframe::UntilLogout => kind.push_str("until you log out"),
-            Timeframe::Timespan { until } => {
-                // TODO handle error better
-                let time = {
-                    if let Ok(tm_fmt) = until.strftime("%R UTC") {
-                        format!("{}", tm_fmt)
-                    } else {
-                        "error formatting time".to_owned()
-                    }
-                };
-                kind.push_str(&format!("until {}", time));
+        .filter(|&(time, _)| !time.is_expired(now))
+        .collect::<Vec<_>>();
+    timeframes.sort_by_key(|&(time, _)| expiry_order(time));
+    if timeframes.is_empty() {
+        reply.text.push_str(" You're not up for Stronghold.");
+    }
+    for tier in TIERS.iter() {
+        let lines = timeframes.iter()
+            .filter(|&&(_, wants)| wants.contains(&Want { tier: *tier }))
+            .map(|&(time, _)| match *time {
+                Timeframe::Timespan { until } => {
+                    format!("{} ({} left)",
+                            describe_timeframe(time, now, tz),
+                            format_remaining(until - *now))
+                }
+                _ => describe_timeframe(time, now, tz),
+            })
+            .collect::<Vec<String>>();
+        if !lines.is_empty() {
+            reply.fields.push((format!("Tier {}", tier.number()), lines.join("\n")));
+        }
+    }
+    let timezone = match ud.timezone {
+        Some(tz) => tz.name().to_owned(),
+        None => "UTC (not set)".to_owned(),
+    };
+    reply.fields.push(("Timezone".to_owned(), timezone));
+    let hint = |command, what| format!("`{}` {}", usage(command, vocabulary), what);
+    let hints = [hint(Command::Want, "notes more"),
+                 hint(Command::DontWant, "takes them back"),
+                 hint(Command::Schedules, "lists or removes weekly ones"),
+                 hint(Command::Timezone, "sets your timezone"),
+                 format!("`{} {}` shows all options",
+                         vocabulary.word(Keyword::Help),
+                         command_name(Command::Want, vocabulary))];
+    reply.fields.push(("How to change it".to_owned(), hints.join("\n")));
+    reply
+}
+
+/// Confirms a want, listing everything the user is up for at the moment. The command is the
+/// canonical one for the want, to show how it was understood, if there is one.
+pub fn want(ud: &UserData, now: &time::Tm, command: Option<&str>) -> Reply {
+    let mut reply = Reply::new(format!("Ok, I'll note you're up for Stronghold: {}.",
+                                       format_wants(ud, now)));
+    reply.fields.extend(command.map(registered_as));
+    reply.colour = Some(COLOUR_SUCCESS);
+    reply
+}
+
+/// A field showing the canonical command for what the user asked for.
+fn registered_as(command: &str) -> (String, String) {
+    ("Registered as".to_owned(), format!("`{}`", sanitize_code(command)))
+}
+
+/// Formats a point in time in the timezone relative to now, i.e. only with a day if it's not today.
+fn format_time(tm: &time::Tm, now: &time::Tm, tz: Tz) -> String {
+    let format = match (timezone::local_date(tm, tz), timezone::local_date(now, tz)) {
+        (Some(date), Some(today)) if date == today => "%R %Z",
+        (Some(date), Some(today)) if today.succ_opt() == Some(date) => "tomorrow %R %Z",
+        _ => "%F %R %Z",
+    };
+    timezone::format(tm, tz, format)
+}
+
+/// Formats a weekly schedule, e.g. "every Mon, Wed, Fri from 19:00 to 22:00 (Europe/Berlin)".
+fn format_schedule(days: u8, from: u32, until: u32, tz: Tz) -> String {
+    let days = if days == 0b1111111 {
+        "day".to_owned()
+    } else {
+        WEEKDAYS.iter()
+            .enumerate()
+            .filter(|&(i, _)| days & (1 << i) != 0)
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>()
+            .join(", ")
+    };
+    format!("every {} from {:02}:{:02} to {:02}:{:02} ({})",
+            days,
+            from / 60,
+            from % 60,
+            until / 60,
+            until % 60,
+            tz.name())
+}
+
+/// Formats the tiers of the wants in ascending order, e.g. "tier 6 and 10".
+fn format_tiers(wants: &HashSet<Want>) -> String {
+    let tiers = TIERS.iter()
+        .filter(|&&tier| wants.contains(&Want { tier: tier }))
+        .map(|tier| tier.number().to_string())
+        .collect::<Vec<String>>();
+    format!("tier {}", join_with_and(&tiers))
+}
+
+pub fn schedules(ud: Option<&UserData>, vocabulary: &Vocabulary) -> Reply {
+    let schedules = ud.map(|ud| ud.schedules()).unwrap_or_else(Vec::new);
+    if schedules.is_empty() {
+        let mut reply = Reply::new(format!("You don't have any schedules. Add one with e.g. \"{} \
+                                            10 every mon,wed,fri 19:00-22:00\".",
+                                           command_name(Command::Want, vocabulary)));
+        reply.colour = Some(COLOUR_INFO);
+        return reply;
+    }
+    let tz = ud.map_or(Tz::UTC, UserData::timezone_or_utc);
+    let lines = schedules.iter()
+        .enumerate()
+        .filter_map(|(i, &(timeframe, wants))| match *timeframe {
+            Timeframe::Weekly { days, from, until } => {
+                Some(format!("{}. {} {}",
+                             i + 1,
+                             format_tiers(wants),
+                             format_schedule(days, from, until, tz)))
             }
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    let mut reply = Reply::new(lines.join("\n"));
+    reply.title = Some("Your schedules".to_owned());
+    reply.footer = Some(format!("Remove one with \"{} {} <number>\"",
+                                command_name(Command::Schedules, vocabulary),
+                                vocabulary.word(Keyword::Remove)));
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+pub fn schedule_removed(removed: Option<(Timeframe, HashSet<Want>)>,
+                        number: usize,
+                        tz: Tz,
+                        vocabulary: &Vocabulary)
+                        -> Reply {
+    match removed {
+        Some((Timeframe::Weekly { days, from, until }, wants)) => {
+            let mut reply = Reply::new(format!("Ok, I removed your schedule for {} {}.",
+                                               format_tiers(&wants),
+                                               format_schedule(days, from, until, tz)));
+            reply.colour = Some(COLOUR_SUCCESS);
+            reply
+        }
+        _ => {
+            let mut reply = Reply::new(format!("You don't have a schedule number {}. Type \
+                                                \"{}\" to list yours.",
+                                               number,
+                                               command_name(Command::Schedules, vocabulary)));
+            reply.colour = Some(COLOUR_ERROR);
+            reply
+        }
+    }
+}
+
+pub fn prefix_show(prefix: &str, vocabulary: &Vocabulary) -> Reply {
+    let mut reply = Reply::new(format!("Start your messages in this server with \"{}\" or a \
+                                        mention of me, e.g. \"{} {}\".",
+                                       sanitize(prefix),
+                                       sanitize(prefix),
+                                       command_name(Command::Status, vocabulary)));
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+pub fn prefix_set(prefix: &str) -> Reply {
+    let mut reply = Reply::new(format!("Ok, messages in this server now have to start with \"{}\" \
+                                        or a mention of me.",
+                                       sanitize(prefix)));
+    reply.colour = Some(COLOUR_SUCCESS);
+    reply
+}
+
+/// Replies to someone who isn't allowed to change the setting of the command.
+pub fn setting_not_allowed(command: Command, vocabulary: &Vocabulary) -> Reply {
+    let mut reply = Reply::new(format!("Only the owner of this server and members who may manage \
+                                        it can change the {}.",
+                                       command_name(command, vocabulary)));
+    reply.colour = Some(COLOUR_ERROR);
+    reply
+}
+
+pub fn setting_permissions_unknown(command: Command, vocabulary: &Vocabulary) -> Reply {
+    let mut reply = Reply::new(format!("Sorry, I couldn't check whether you may change the {}. \
+                                        Please try again later.",
+                                       command_name(command, vocabulary)));
+    reply.colour = Some(COLOUR_ERROR);
+    reply
+}
+
+pub fn prefix_private() -> Reply {
+    let mut reply = Reply::new("Private messages don't need a prefix, just write the command. \
+                                Use this in a server to show or change its prefix.");
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+pub fn language_show(language: Language) -> Reply {
+    let mut reply = Reply::new(format!("The commands in this server are in {}. English ones \
+                                        always work as well.",
+                                       language.name()));
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+pub fn language_set(language: Language, vocabulary: &Vocabulary) -> Reply {
+    let mut reply = Reply::new(format!("Ok, the commands in this server are now in {}. Type \"{}\" \
+                                        to see them.",
+                                       language.name(),
+                                       vocabulary.word(Keyword::Help)));
+    reply.colour = Some(COLOUR_SUCCESS);
+    reply
+}
+
+pub fn language_private() -> Reply {
+    let mut reply = Reply::new("Private messages understand commands in all languages. Use this \
+                                in a server to show or change its language.");
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+pub fn timezone_set(ud: &UserData) -> Reply {
+    let tz = ud.timezone_or_utc();
+    let mut reply = Reply::new(format!("Ok, I'll show you times in {} (where it's currently {}).",
+                                       tz.name(),
+                                       timezone::format(&time::now_utc(), tz, "%R %Z")));
+    reply.colour = Some(COLOUR_SUCCESS);
+    reply
+}
+
+pub fn timezone_show(timezone: Option<Tz>, vocabulary: &Vocabulary) -> Reply {
+    let text = match timezone {
+        Some(tz) => {
+            format!("Your timezone is {} (where it's currently {}).",
+                    tz.name(),
+                    timezone::format(&time::now_utc(), tz, "%R %Z"))
+        }
+        None => {
+            let timezone = command_name(Command::Timezone, vocabulary);
+            format!("You haven't set a timezone, so I'm showing you times in UTC. Tell me yours \
+                     with \"{} <name>\", e.g. \"{} Europe/Berlin\".",
+                    timezone,
+                    timezone)
+        }
+    };
+    let mut reply = Reply::new(text);
+    reply.colour = Some(COLOUR_INFO);
+    reply
+}
+
+/// Confirms a dont want. The command is the canonical one for it, to show how it was understood,
+/// if there is one.
+pub fn dont_want(ud: Option<&UserData>, now: &time::Tm, command: Option<&str>) -> Reply {
+    let remaining = ud.map(|ud| format_wants(ud, now)).unwrap_or_else(String::new);
+    let mut reply = if remaining.is_empty() {
+        Reply::new("Ok, I'll take you off the list.")
+    } else {
+        Reply::new(format!("Ok, you're now only up for Stronghold: {}.", remaining))
+    };
+    reply.fields.extend(command.map(registered_as));
+    reply.colour = Some(COLOUR_SUCCESS);
+    reply
+}
+
+/// Lists the players who are up for each tier, with their end times in the timezone.
+pub fn status(status_report: &StatusReport, now: &time::Tm, tz: Tz) -> Reply {
+    let text = match status_report.num_wanting_total {
+        1 => "There is currently 1 player who wants to play Stronghold.".to_owned(),
+        n => format!("There are currently {} players who want to play Stronghold.", n),
+    };
+    let fields = status_report.tiers
+        .iter()
+        .map(|&(tier, ref players)| {
+            (format!("Tier {} ({})", tier.number(), players.len()),
+             format_players(players, now, tz))
+        })
+        .collect();
+    Reply {
+        title: Some("Stronghold status".to_owned()),
+        text: text,
+        fields: fields,
+        footer: Some("Players who are offline aren't listed".to_owned()),
+        timestamp: Some(*now),
+        colour: Some(COLOUR_INFO),
+    }
+}
+
+/// Lists players one per line, e.g. "Alice (idle): until 22:30 UTC". Only the first
+/// `MAX_PLAYERS_LISTED` are listed by name, players without a known name as "user <id>".
+fn format_players(players: &[Player], now: &time::Tm, tz: Tz) -> String {
+    if players.is_empty() {
+        return "Nobody".to_owned();
+    }
+    let mut lines = players.iter()
+        .take(MAX_PLAYERS_LISTED)
+        .map(|player| {
+            let name = match player.name {
+                Some(ref name) => sanitize(name),
+                // Not as a mention, which would notify them.
+                None => format!("user {}", player.user_id.0),
+            };
+            let idle = if player.status == OnlineStatus::Idle {
+                " (idle)"
+            } else {
+                ""
+            };
+            let until = match player.until {
+                WantEnd::At(until) => format!("until {}", format_time(&until, now, tz)),
+                WantEnd::Logout => "until logout".to_owned(),
+                WantEnd::Never => "always".to_owned(),
+            };
+            format!("{}{}: {}", name, idle, until)
+        })
+        .collect::<Vec<String>>();
+    if players.len() > MAX_PLAYERS_LISTED {
+        lines.push(format!("and {} more", players.len() - MAX_PLAYERS_LISTED));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests_invalid_request {
+    use super::{invalid_request, unknown_request};
+    use model::Command;
+    use vocabulary::{Vocabulary, Language};
+
+    fn en() -> Vocabulary {
+        Vocabulary::new(Language::English)
+    }
+
+    #[test]
+    fn shows_offending_token_and_usage() {
+        let reply =
+            invalid_request("want 10 for 2x",
+                            Command::Want,
+                            "Unknown unit \"x\".",
+                            12,
+                            None,
+                            &en());
+        assert_eq!("\"want 10 for 2x\" is not a valid want request. Problem at \"2x\": Unknown \
+                    unit \"x\".",
+                   reply.text);
+        assert_eq!(vec![("Usage".to_owned(),
+                         "`want [<tiers>] [<timeframe>]`\nType `help want` for more.".to_owned())],
+                   reply.fields);
+    }
+
+    #[test]
+    fn missing_token() {
+        let reply =
+            invalid_request("dont", Command::DontWant, "Expected \"want\".", 4, None, &en());
+        assert_eq!("\"dont\" is not a valid dont want request. Problem at the end: Expected \
+                    \"want\".",
+                   reply.text);
+        assert_eq!(vec![("Usage".to_owned(),
+                         "`dont want [<tiers>] [<timeframe kinds>]`\nType `help dont want` for \
+                          more."
+                             .to_owned())],
+                   reply.fields);
+    }
+
+    #[test]
+    fn in_vocabulary_language() {
+        let reply = invalid_request("nicht",
+                                    Command::DontWant,
+                                    "Expected \"will\".",
+                                    5,
+                                    None,
+                                    &Vocabulary::new(Language::German));
+        assert_eq!("\"nicht\" is not a valid nicht will request. Problem at the end: Expected \
+                    \"will\".",
+                   reply.text);
+        assert_eq!(vec![("Usage".to_owned(),
+                         "`nicht will [<tiers>] [<timeframe kinds>]`\nType `hilfe nicht will` \
+                          for more."
+                             .to_owned())],
+                   reply.fields);
+    }
+
+    #[test]
+    fn sanitizes_offending_token() {
+        let reply =
+            invalid_request("dont @everyone",
+                            Command::DontWant,
+                            "Got \"@everyone\".",
+                            5,
+                            None,
+                            &en());
+        assert!(!reply.text.contains("@everyone"));
+    }
+
+    #[test]
+    fn suggestion() {
+        let reply = invalid_request("want 10 alwyas",
+                                    Command::Want,
+                                    "Expected a tier.",
+                                    8,
+                                    Some(".sh want 10 always"),
+                                    &en());
+        assert_eq!("\"want 10 alwyas\" is not a valid want request. Problem at \"alwyas\": \
+                    Expected a tier. Did you mean `.sh want 10 always`?",
+                   reply.text);
+    }
+
+    #[test]
+    fn unknown_with_suggestion() {
+        let reply = unknown_request("wnat 10", Some(".sh want 10"), &en());
+        assert_eq!("\"wnat 10\" is not a valid request. Did you mean `.sh want 10`? Type \
+                    \"help\" to find out what is.",
+                   reply.text);
+        let reply = unknown_request("wnat `10`\n", Some("want `10`\n"), &en());
+        assert!(reply.text.contains("Did you mean `want '10' `?"));
+        let reply = unknown_request("hello", None, &en());
+        assert_eq!("\"hello\" is not a valid request. Type \"help\" to find out what is.",
+                   reply.text);
+    }
+}
+
+#[cfg(test)]
+mod tests_help {
+    use super::{help, command_help};
+    use commands::COMMANDS;
+    use model::Command;
+    use vocabulary::{Vocabulary, Language};
+
+    #[test]
+    fn lists_every_command() {
+        let reply = help(&Vocabulary::new(Language::English));
+        assert_eq!(COMMANDS.len() + 1, reply.fields.len());
+        assert_eq!(("want, w".to_owned(),
+                    "`want [<tiers>] [<timeframe>]` Notes that you're up for Stronghold."
+                        .to_owned()),
+                   reply.fields[1]);
+        assert_eq!(("dont want".to_owned(),
+                    "`dont want [<tiers>] [<timeframe kinds>]` Takes you off the list.".to_owned()),
+                   reply.fields[2]);
+    }
+
+    #[test]
+    fn german() {
+        let reply = help(&Vocabulary::new(Language::German));
+        assert!(reply.text.ends_with("Type `hilfe <command>` to find out more about one, e.g. \
+                                      `hilfe will`."));
+        assert_eq!(("hilfe, ?, help".to_owned(),
+                    "`hilfe [<command>]` Shows the commands, or how to use one of them."
+                        .to_owned()),
+                   reply.fields[0]);
+        assert_eq!("nicht will", reply.fields[2].0);
+        assert_eq!(("Examples".to_owned(),
+                    "`hilfe will`\n`will 8 10`\n`nicht will 10`\n`zeitpläne entfernen 2`\n\
+                     `zeitzone Europe/Berlin`\n`präfix !sh`\n`sprache Deutsch`"
+                        .to_owned()),
+                   reply.fields[COMMANDS.len()]);
+    }
+
+    #[test]
+    fn want() {
+        let reply = command_help(Command::Want, &Vocabulary::new(Language::English));
+        assert_eq!(Some("Help: want, w".to_owned()), reply.title);
+        let names = reply.fields.iter().map(|&(ref name, _)| &**name).collect::<Vec<&str>>();
+        assert_eq!(vec!["Usage", "<tiers>", "<timeframe>", "Values", "Phrases", "Examples"],
+                   names);
+        assert_eq!("`6`, `8`, `10`: a single tier\n`t10`, `tier10`, `X`: a single tier, written \
+                    differently\n`8+`: a tier and the ones above it\n`6-8`, `all`: the tiers \
+                    from one to another, or all of them\nSeveral can be given, e.g. `6 10`.",
+                   reply.fields[1].1);
+        assert!(reply.fields[2].1.contains("\n`until logout | until [tomorrow] <time>` (or till, \
+                                            til): until you log out (the default), or until the \
+                                            time\n"));
+        assert!(reply.fields[4].1.starts_with("`tonight` means `from 18:00 to 00:00`\n"));
+        assert_eq!("`want 8 10`\n`want 10 every mon,wed,fri 19:00-22:00`\n`want 6 always`",
+                   reply.fields[5].1);
+    }
+
+    #[test]
+    fn dont_want() {
+        let reply = command_help(Command::DontWant, &Vocabulary::new(Language::German));
+        assert_eq!(Some("Help: nicht will".to_owned()), reply.title);
+        assert_eq!(("Usage".to_owned(), "`nicht will [<tiers>] [<timeframe kinds>]`".to_owned()),
+                   reply.fields[0]);
+        assert_eq!(("<timeframe kinds>".to_owned(),
+                    "`always`: wants until you take them back\n`until` (or till, til): wants \
+                     until you log out or until a time\n`for`: wants until a time\n`from`: \
+                     wants from one time to another\n`every`: weekly schedules"
+                        .to_owned()),
+                   reply.fields[2]);
+        assert_eq!(("Examples".to_owned(),
+                    "`nicht will`\n`nicht will 10`\n`nicht will 6 8 every`".to_owned()),
+                   reply.fields[3]);
+    }
+
+    #[test]
+    fn without_tables() {
+        let reply = command_help(Command::Status, &Vocabulary::new(Language::English));
+        assert_eq!(vec![("Usage".to_owned(), "`status`".to_owned()),
+                        ("Examples".to_owned(), "`status`".to_owned())],
+                   reply.fields);
+    }
+}
+
+#[cfg(test)]
+mod tests_format_time {
+    use super::format_time;
+    use chrono_tz::Tz;
+    use time;
+
+    #[test]
+    fn relative_day() {
+        // 2016-09-27 18:13:20 UTC
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        let at = |sec| time::at_utc(time::Timespec::new(sec, 0));
+        assert_eq!("22:30 UTC", format_time(&at(1475015400), &now, Tz::UTC));
+        assert_eq!("tomorrow 01:00 UTC", format_time(&at(1475024400), &now, Tz::UTC));
+        assert_eq!("2016-09-29 01:00 UTC", format_time(&at(1475110800), &now, Tz::UTC));
+        assert_eq!("2016-09-26 23:59 UTC", format_time(&at(1474934340), &now, Tz::UTC));
+    }
+
+    #[test]
+    fn timezone() {
+        // 2016-09-27 18:13:20 UTC, 20:13:20 in Berlin.
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        let at = |sec| time::at_utc(time::Timespec::new(sec, 0));
+        // 2016-09-27 22:30 UTC
+        assert_eq!("tomorrow 00:30 CEST",
+                   format_time(&at(1475015400), &now, Tz::Europe__Berlin));
+        assert_eq!("18:30 EDT", format_time(&at(1475015400), &now, Tz::America__New_York));
+    }
+}
+
+#[cfg(test)]
+mod tests_want {
+    use super::want;
+    use model::{UserData, Timeframe, Want, Tier};
+    use discord::model::OnlineStatus;
+    use chrono_tz::Tz;
+    use time;
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    fn user_data(time_wants: &[(Timeframe, &[Tier])], tz: Tz) -> UserData {
+        let mut ud = UserData::new(OnlineStatus::Online);
+        ud.timezone = Some(tz);
+        for &(time, tiers) in time_wants {
+            ud.time_wants.insert(time, tiers.iter().map(|&tier| Want { tier: tier }).collect());
         }
-        if i + 2 < num_wants {
-            // Before second-to-last one, add comma for enumeration.
-            kind.push_str(", ");
-        } else if i + 2 == num_wants {
-            // Second-to-last one, add "and".
-            kind.push_str(" and ");
+        ud
+    }
+
+    /// 2016-09-27 18:13:20 UTC
+    fn now() -> time::Tm {
+        at(1475000000)
+    }
+
+    #[test]
+    fn groups_tiers_by_timeframe() {
+        // 21:30 UTC
+        let until = Timeframe::Timespan { until: at(1475011800) };
+        let ud = user_data(&[(until, &[Tier::Tier10]),
+                             (Timeframe::UntilLogout, &[Tier::Tier8, Tier::Tier6])],
+                           Tz::UTC);
+        assert_eq!("Ok, I'll note you're up for Stronghold: tier 6 and 8 until you log out, tier \
+                    10 until 21:30 UTC.",
+                   want(&ud, &now(), Some("want 10 until 21:30")).text);
+    }
+
+    #[test]
+    fn orders_timeframes_by_expiry() {
+        let weekly = Timeframe::Weekly {
+            days: 0b0010101,
+            from: 19 * 60,
+            until: 22 * 60,
+        };
+        // Tomorrow 20:00 to 23:00 CEST.
+        let window = Timeframe::Window {
+            from: at(1475085600),
+            until: at(1475096400),
+        };
+        let all: &[Tier] = &[Tier::Tier10, Tier::Tier8, Tier::Tier6];
+        let time_wants: Vec<(Timeframe, &[Tier])> =
+            vec![(Timeframe::Always, all),
+                 (weekly, &[Tier::Tier10]),
+                 (window, &[Tier::Tier8]),
+                 // 22:30 and 20:15 CEST.
+                 (Timeframe::Timespan { until: at(1475015400) }, &[Tier::Tier6]),
+                 (Timeframe::Timespan { until: at(1475000100) }, &[Tier::Tier8]),
+                 // Expired.
+                 (Timeframe::Timespan { until: at(1474999999) }, &[Tier::Tier6])];
+        let expected = "Ok, I'll note you're up for Stronghold: tier 8 until 20:15 CEST, tier 6 \
+                        until tomorrow 00:30 CEST, tier 8 from tomorrow 20:00 CEST until \
+                        tomorrow 23:00 CEST, tier 10 every Mon, Wed, Fri from 19:00 to 22:00 \
+                        (Europe/Berlin), tier 6, 8 and 10 whenever you're online.";
+        // The order doesn't depend on the order the wants were added in.
+        for i in 0..time_wants.len() {
+            let rotated = time_wants[i..]
+                .iter()
+                .chain(time_wants[..i].iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            let ud = user_data(&rotated, Tz::Europe__Berlin);
+            assert_eq!(expected, want(&ud, &now(), Some("want always")).text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_dont_want {
+    use super::dont_want;
+    use model::{UserData, Timeframe, Want, Tier};
+    use discord::model::OnlineStatus;
+    use time;
+
+    #[test]
+    fn summarizes_remaining_wants() {
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        let mut ud = UserData::new(OnlineStatus::Online);
+        assert_eq!("Ok, I'll take you off the list.",
+                   dont_want(None, &now, Some("dont want")).text);
+        assert_eq!("Ok, I'll take you off the list.",
+                   dont_want(Some(&ud), &now, Some("dont want")).text);
+        ud.time_wants.insert(Timeframe::Timespan { until: now },
+                             vec![Want { tier: Tier::Tier6 }].into_iter().collect());
+        // Expired wants don't count.
+        assert_eq!("Ok, I'll take you off the list.",
+                   dont_want(Some(&ud), &now, Some("dont want 8")).text);
+        ud.time_wants.insert(Timeframe::Always,
+                             vec![Want { tier: Tier::Tier8 }].into_iter().collect());
+        assert_eq!("Ok, you're now only up for Stronghold: tier 8 whenever you're online.",
+                   dont_want(Some(&ud), &now, Some("dont want 6 10")).text);
+    }
+
+    #[test]
+    fn single_spaces() {
+        let now = time::at_utc(time::Timespec::new(1475000000, 0));
+        let mut ud = UserData::new(OnlineStatus::Online);
+        ud.time_wants.insert(Timeframe::UntilLogout,
+                             vec![Want { tier: Tier::Tier6 }, Want { tier: Tier::Tier10 }]
+                                 .into_iter()
+                                 .collect());
+        ud.time_wants.insert(Timeframe::Timespan { until: now + time::Duration::hours(2) },
+                             vec![Want { tier: Tier::Tier8 }].into_iter().collect());
+        let text = dont_want(Some(&ud), &now, Some("dont want always")).text;
+        assert!(!text.contains("  "), "{}", text);
+    }
+
+    #[test]
+    fn shows_command() {
+        let now = time::now_utc();
+        assert_eq!(vec![("Registered as".to_owned(), "`.sh dont want 6 10 always`".to_owned())],
+                   dont_want(None, &now, Some(".sh dont want 6 10 always")).fields);
+        // Backticks in the prefix can't end the code.
+        assert_eq!(vec![("Registered as".to_owned(), "`'sh dont want`".to_owned())],
+                   dont_want(None, &now, Some("`sh dont want")).fields);
+        // Without a command, there's nothing to show.
+        assert!(dont_want(None, &now, None).fields.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_schedules {
+    use super::{schedules, schedule_removed};
+    use model::{UserData, Timeframe, Want, Tier, TIERS};
+    use vocabulary::{Vocabulary, Language};
+    use discord::model::OnlineStatus;
+    use chrono_tz::Tz;
+
+    fn en() -> Vocabulary {
+        Vocabulary::new(Language::English)
+    }
+
+    fn de() -> Vocabulary {
+        Vocabulary::new(Language::German)
+    }
+
+    fn weekly(days: u8, from: u32, until: u32) -> Timeframe {
+        Timeframe::Weekly {
+            days: days,
+            from: from,
+            until: until,
         }
     }
-    format!("Ok, I'll note you're up for {}.", kind)
+
+    #[test]
+    fn none() {
+        assert_eq!("You don't have any schedules. Add one with e.g. \"want 10 every mon,wed,fri \
+                    19:00-22:00\".",
+                   schedules(None, &en()).text);
+        let mut ud = UserData::new(OnlineStatus::Online);
+        ud.time_wants.insert(Timeframe::Always,
+                             vec![Want { tier: Tier::Tier6 }].into_iter().collect());
+        assert_eq!(schedules(None, &en()), schedules(Some(&ud), &en()));
+        assert_eq!("You don't have any schedules. Add one with e.g. \"will 10 every mon,wed,fri \
+                    19:00-22:00\".",
+                   schedules(None, &de()).text);
+    }
+
+    #[test]
+    fn numbered_in_order() {
+        let mut ud = UserData::new(OnlineStatus::Online);
+        ud.timezone = Some(Tz::Europe__Berlin);
+        ud.time_wants.insert(weekly(0b1111111, 9 * 60, 30),
+                             vec![Want { tier: Tier::Tier6 }].into_iter().collect());
+        ud.time_wants.insert(weekly(0b0010101, 19 * 60, 22 * 60),
+                             vec![Want { tier: Tier::Tier10 }, Want { tier: Tier::Tier6 }]
+                                 .into_iter()
+                                 .collect());
+        ud.time_wants.insert(weekly(0b0100000, 19 * 60, 22 * 60),
+                             vec![Want { tier: Tier::Tier8 }].into_iter().collect());
+        let reply = schedules(Some(&ud), &en());
+        assert_eq!("1. tier 6 every day from 09:00 to 00:30 (Europe/Berlin)\n\
+                    2. tier 6 and 10 every Mon, Wed, Fri from 19:00 to 22:00 (Europe/Berlin)\n\
+                    3. tier 8 every Sat from 19:00 to 22:00 (Europe/Berlin)",
+                   reply.text);
+        assert_eq!(Some("Remove one with \"schedules remove <number>\"".to_owned()), reply.footer);
+        assert_eq!(Some("Remove one with \"zeitpläne entfernen <number>\"".to_owned()),
+                   schedules(Some(&ud), &de()).footer);
+    }
+
+    #[test]
+    fn removed() {
+        let wants = vec![Want { tier: Tier::Tier8 }].into_iter().collect();
+        assert_eq!("Ok, I removed your schedule for tier 8 every Tue from 19:00 to 22:00 (UTC).",
+                   schedule_removed(Some((weekly(0b10, 19 * 60, 22 * 60), wants)),
+                                    1,
+                                    Tz::UTC,
+                                    &en())
+                       .text);
+        let wants = TIERS.iter().map(|&tier| Want { tier: tier }).collect();
+        assert_eq!("Ok, I removed your schedule for tier 6, 8 and 10 every Tue from 19:00 to \
+                    22:00 (UTC).",
+                   schedule_removed(Some((weekly(0b10, 19 * 60, 22 * 60), wants)),
+                                    1,
+                                    Tz::UTC,
+                                    &en())
+                       .text);
+        assert_eq!("You don't have a schedule number 3. Type \"schedules\" to list yours.",
+                   schedule_removed(None, 3, Tz::UTC, &en()).text);
+        assert_eq!("You don't have a schedule number 3. Type \"zeitpläne\" to list yours.",
+                   schedule_removed(None, 3, Tz::UTC, &de()).text);
+    }
 }
 
-pub fn dont_want() -> String {
-    "Ok, I'll take you off the list.".to_owned()
+#[cfg(test)]
+mod tests_sanitize {
+    use super::{sanitize, MAX_QUOTE_LEN};
+    use std::iter;
+
+    #[test]
+    fn plain_text_unchanged() {
+        assert_eq!("want 10 for 1:30h", sanitize("want 10 for 1:30h"));
+        assert_eq!("ßDf äöü 10", sanitize("ßDf äöü 10"));
+    }
+
+    #[test]
+    fn everyone_and_here() {
+        assert_eq!("@\u{200B}everyone", sanitize("@everyone"));
+        assert_eq!("hi @\u{200B}here", sanitize("hi @here"));
+    }
+
+    #[test]
+    fn user_mention() {
+        assert_eq!("<@\u{200B}1234>", sanitize("<@1234>"));
+        assert_eq!("<@\u{200B}!1234>", sanitize("<@!1234>"));
+    }
+
+    #[test]
+    fn role_mention() {
+        assert_eq!("<@\u{200B}&1234>", sanitize("<@&1234>"));
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!("\\*\\*bold\\*\\* \\_it\\_ \\~\\~strike\\~\\~ \\|\\|spoiler\\|\\|",
+                   sanitize("**bold** _it_ ~~strike~~ ||spoiler||"));
+        assert_eq!("\\> quote", sanitize("> quote"));
+        assert_eq!("a > b", sanitize("a > b"));
+        assert_eq!("back\\\\slash", sanitize("back\\slash"));
+    }
+
+    #[test]
+    fn code_fence() {
+        assert_eq!("\\`\\`\\`rust code\\`\\`\\`", sanitize("```rust code```"));
+        assert_eq!("\\`inline\\`", sanitize("`inline`"));
+    }
+
+    #[test]
+    fn newlines_collapsed() {
+        assert_eq!("want  10 \\`\\`\\` x", sanitize("want\n\t10\n```\rx"));
+    }
+
+    #[test]
+    fn truncated() {
+        let long = iter::repeat("ß").take(MAX_QUOTE_LEN + 1).collect::<String>();
+        let expected = iter::repeat("ß").take(MAX_QUOTE_LEN).collect::<String>() + "...";
+        assert_eq!(expected, sanitize(&long));
+        let exact = iter::repeat("a").take(MAX_QUOTE_LEN).collect::<String>();
+        assert_eq!(exact, sanitize(&exact));
+    }
+
+    #[test]
+    fn truncated_before_escaping() {
+        let long = iter::repeat("@").take(MAX_QUOTE_LEN + 5).collect::<String>();
+        let expected = iter::repeat("@\u{200B}").take(MAX_QUOTE_LEN).collect::<String>() + "...";
+        assert_eq!(expected, sanitize(&long));
+    }
 }
 
-pub fn status(status_report: &StatusReport) -> String {
-    // TODO special case one player (is/are)
-    // TODO better solution for multiline strings?
-    format!("There is currently a total of {} players who want to play Stronghold.
-{} want tier 6, {} tier 8 and {} tier 10.",
-            status_report.num_wanting_total,
-            status_report.num_wanting_t6,
-            status_report.num_wanting_t8,
-            status_report.num_wanting_t10)
+#[cfg(test)]
+mod tests_status {
+    use super::status;
+    use model::{StatusReport, Player, Tier, WantEnd};
+    use discord::model::{UserId, OnlineStatus};
+    use chrono_tz::Tz;
+    use time;
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    fn player(id: u64, name: Option<&str>, status: OnlineStatus, until: WantEnd) -> Player {
+        Player {
+            user_id: UserId(id),
+            name: name.map(str::to_owned),
+            status: status,
+            until: until,
+        }
+    }
+
+    #[test]
+    fn lists_players() {
+        // 2016-09-27 18:13:20 UTC
+        let now = at(1475000000);
+        // 22:30 UTC
+        let until = at(1475015400);
+        let report = StatusReport {
+            num_wanting_total: 3,
+            tiers: vec![(Tier::Tier6, vec![]),
+                        (Tier::Tier8,
+                         vec![player(1, Some("Alice"), OnlineStatus::Online, WantEnd::At(until)),
+                              player(2, None, OnlineStatus::Idle, WantEnd::Logout)]),
+                        (Tier::Tier10,
+                         vec![player(3, Some("*Bob*"), OnlineStatus::Online, WantEnd::Never)])],
+        };
+        let reply = status(&report, &now, Tz::Europe__Berlin);
+        assert_eq!("There are currently 3 players who want to play Stronghold.", reply.text);
+        assert_eq!(vec![("Tier 6 (0)".to_owned(), "Nobody".to_owned()),
+                        ("Tier 8 (2)".to_owned(),
+                         "Alice: until tomorrow 00:30 CEST\nuser 2 (idle): until logout"
+                             .to_owned()),
+                        ("Tier 10 (1)".to_owned(), "\\*Bob\\*: always".to_owned())],
+                   reply.fields);
+    }
+
+    #[test]
+    fn one_player() {
+        let report = StatusReport {
+            num_wanting_total: 1,
+            tiers: vec![(Tier::Tier6,
+                         vec![player(1, None, OnlineStatus::Online, WantEnd::Logout)])],
+        };
+        let reply = status(&report, &at(0), Tz::UTC);
+        assert_eq!("There is currently 1 player who wants to play Stronghold.", reply.text);
+    }
+
+    #[test]
+    fn many_players() {
+        let players = (0..20)
+            .map(|id| player(id, None, OnlineStatus::Online, WantEnd::Never))
+            .collect::<Vec<Player>>();
+        let report = StatusReport {
+            num_wanting_total: 20,
+            tiers: vec![(Tier::Tier10, players)],
+        };
+        let reply = status(&report, &at(0), Tz::UTC);
+        let lines = reply.fields[0].1.lines().collect::<Vec<&str>>();
+        assert_eq!(13, lines.len());
+        assert_eq!("and 8 more", lines[12]);
+    }
+
+    #[test]
+    fn no_mentions() {
+        // Players whose names aren't known yet, which used to be mentioned instead.
+        let report = StatusReport {
+            num_wanting_total: 2,
+            tiers: vec![(Tier::Tier8, vec![player(1, None, OnlineStatus::Online, WantEnd::Never)]),
+                        (Tier::Tier10, vec![player(2, None, OnlineStatus::Idle, WantEnd::Logout)])],
+        };
+        let reply = status(&report, &at(0), Tz::UTC);
+        assert!(!reply.text.contains("<@"));
+        for &(ref name, ref value) in &reply.fields {
+            assert!(!name.contains("<@") && !value.contains("<@"), "{}", value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_me {
+    use super::me;
+    use model::{UserData, Timeframe, Tier, Want};
+    use vocabulary::{Vocabulary, Language};
+    use discord::model::OnlineStatus;
+    use chrono_tz::Tz;
+    use time;
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    fn add(ud: &mut UserData, time: Timeframe, tiers: &[Tier]) {
+        ud.time_wants.insert(time, tiers.iter().map(|&tier| Want { tier: tier }).collect());
+    }
+
+    #[test]
+    fn lists_wants_by_tier() {
+        // 2016-09-27 18:13:20 UTC
+        let now = at(1475000000);
+        let mut ud = UserData::new(OnlineStatus::Idle);
+        ud.timezone = Some(Tz::Europe__Berlin);
+        add(&mut ud, Timeframe::Always, &[Tier::Tier10]);
+        add(&mut ud, Timeframe::UntilLogout, &[Tier::Tier6, Tier::Tier8]);
+        add(&mut ud, Timeframe::Timespan { until: at(1475003900) }, &[Tier::Tier6]);
+        // Expired, but not removed yet.
+        add(&mut ud, Timeframe::Timespan { until: at(100) }, &[Tier::Tier6]);
+        let weekly = Timeframe::Weekly {
+            days: 1,
+            from: 19 * 60,
+            until: 22 * 60,
+        };
+        add(&mut ud, weekly, &[Tier::Tier10]);
+        let reply = me(Some(&ud), &now, &Vocabulary::new(Language::English));
+        assert_eq!("I see you as idle, your wants still count.", reply.text);
+        let field = |name: &str, value: &str| (name.to_owned(), value.to_owned());
+        assert_eq!(vec![field("Tier 6", "until you log out\nuntil 21:18 CEST (1h 5m left)"),
+                        field("Tier 8", "until you log out"),
+                        field("Tier 10",
+                              "every Mon from 19:00 to 22:00 (Europe/Berlin)\nwhenever you're \
+                               online"),
+                        field("Timezone", "Europe/Berlin"),
+                        field("How to change it",
+                              "`want [<tiers>] [<timeframe>]` notes more\n`dont want [<tiers>] \
+                               [<timeframe kinds>]` takes them back\n`schedules [remove \
+                               <number>]` lists or removes weekly ones\n`timezone [<name from \
+                               the tz database>]` sets your timezone\n`help want` shows all \
+                               options")],
+                   reply.fields);
+    }
+
+    #[test]
+    fn nothing_wanted() {
+        let ud = UserData::new(OnlineStatus::Offline);
+        let reply = me(Some(&ud), &at(0), &Vocabulary::new(Language::German));
+        assert_eq!("I see you as offline, so your wants only count once you're back. You're \
+                    not up for Stronghold.",
+                   reply.text);
+        assert_eq!(("Timezone".to_owned(), "UTC (not set)".to_owned()), reply.fields[0]);
+        assert!(reply.fields[1].1.starts_with("`will [<tiers>] [<timeframe>]` notes more\n"));
+    }
+
+    #[test]
+    fn unknown_user() {
+        let reply = me(None, &at(0), &Vocabulary::new(Language::English));
+        assert_eq!("Nothing yet. Type `want` to note you're up for Stronghold.", reply.text);
+        assert!(reply.fields.is_empty());
+    }
 }
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use discord::model::{Event, PossibleServer, ServerId, UserId, RoleId, Role, Permissions};
+use discord::model::permissions::{MANAGE_ROLES, MANAGE_SERVER};
+
+/// The owner of a server and the permissions of its roles.
+struct CachedServer {
+    owner_id: UserId,
+    role_permissions: HashMap<RoleId, Permissions>,
+}
+
+/// Keeps track of the servers the bot is on, as far as needed to check who may change a server's
+/// settings. Discord only tells us about them through events, so every event has to be passed to
+/// `update()`.
+pub struct ServerCache {
+    servers: HashMap<ServerId, CachedServer>,
+}
+
+impl ServerCache {
+    pub fn new() -> Self {
+        ServerCache { servers: HashMap::new() }
+    }
+
+    pub fn update(&mut self, event: &Event) {
+        match *event {
+            Event::ServerCreate(PossibleServer::Online(ref server)) => {
+                self.set_server(server.id, server.owner_id, &server.roles);
+            }
+            Event::ServerUpdate(ref server) => {
+                self.set_server(server.id, server.owner_id, &server.roles);
+            }
+            Event::ServerDelete(PossibleServer::Online(ref server)) => {
+                self.servers.remove(&server.id);
+            }
+            Event::ServerDelete(PossibleServer::Offline(server_id)) => {
+                self.servers.remove(&server_id);
+            }
+            Event::ServerRoleCreate(server_id, ref role) |
+            Event::ServerRoleUpdate(server_id, ref role) => {
+                if let Some(server) = self.servers.get_mut(&server_id) {
+                    server.role_permissions.insert(role.id, role.permissions);
+                }
+            }
+            Event::ServerRoleDelete(server_id, role_id) => {
+                if let Some(server) = self.servers.get_mut(&server_id) {
+                    server.role_permissions.remove(&role_id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_server(&mut self, server_id: ServerId, owner_id: UserId, roles: &[Role]) {
+        self.servers.insert(server_id,
+                            CachedServer {
+                                owner_id: owner_id,
+                                role_permissions: roles.iter()
+                                    .map(|role| (role.id, role.permissions))
+                                    .collect(),
+                            });
+    }
+
+    /// Whether the user with the given roles may change the server's settings, i.e. owns the server
+    /// or has a role (including @everyone) that allows managing it. Returns None if the server is
+    /// unknown.
+    pub fn may_manage(&self, server_id: ServerId, user_id: UserId, roles: &[RoleId])
+                      -> Option<bool> {
+        self.servers.get(&server_id).map(|server| {
+            if server.owner_id == user_id {
+                return true;
+            }
+            // The @everyone role has the same ID as the server.
+            let everyone = RoleId(server_id.0);
+            roles.iter().chain(Some(&everyone)).any(|role_id| {
+                // The discord library calls the administrator permission MANAGE_ROLES.
+                server.role_permissions
+                    .get(role_id)
+                    .map_or(false, |p| p.contains(MANAGE_ROLES) || p.contains(MANAGE_SERVER))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests_server_cache {
+    use super::ServerCache;
+    use discord::model::{Event, PossibleServer, ServerId, UserId, RoleId, Role, Permissions};
+    use discord::model::permissions::{MANAGE_ROLES, MANAGE_SERVER, SEND_MESSAGES};
+
+    fn role(id: u64, permissions: Permissions) -> Role {
+        Role {
+            id: RoleId(id),
+            name: format!("role {}", id),
+            color: 0,
+            hoist: false,
+            managed: false,
+            position: 0,
+            mentionable: false,
+            permissions: permissions,
+        }
+    }
+
+    fn cache() -> ServerCache {
+        let mut cache = ServerCache::new();
+        // Role 1 is @everyone.
+        cache.set_server(ServerId(1),
+                         UserId(10),
+                         &[role(1, SEND_MESSAGES), role(2, MANAGE_SERVER), role(3, MANAGE_ROLES)]);
+        cache
+    }
+
+    #[test]
+    fn owner_and_roles() {
+        let cache = cache();
+        assert_eq!(Some(true), cache.may_manage(ServerId(1), UserId(10), &[]));
+        assert_eq!(Some(false), cache.may_manage(ServerId(1), UserId(11), &[]));
+        assert_eq!(Some(false), cache.may_manage(ServerId(1), UserId(11), &[RoleId(4)]));
+        assert_eq!(Some(true), cache.may_manage(ServerId(1), UserId(11), &[RoleId(2)]));
+        assert_eq!(Some(true), cache.may_manage(ServerId(1), UserId(11), &[RoleId(3)]));
+        assert_eq!(None, cache.may_manage(ServerId(2), UserId(10), &[]));
+    }
+
+    #[test]
+    fn role_events() {
+        let mut cache = cache();
+        cache.update(&Event::ServerRoleUpdate(ServerId(1), role(1, MANAGE_SERVER)));
+        assert_eq!(Some(true), cache.may_manage(ServerId(1), UserId(11), &[]));
+        cache.update(&Event::ServerRoleDelete(ServerId(1), RoleId(1)));
+        assert_eq!(Some(false), cache.may_manage(ServerId(1), UserId(11), &[]));
+        cache.update(&Event::ServerRoleCreate(ServerId(1), role(4, MANAGE_SERVER)));
+        assert_eq!(Some(true), cache.may_manage(ServerId(1), UserId(11), &[RoleId(4)]));
+        cache.update(&Event::ServerDelete(PossibleServer::Offline(ServerId(1))));
+        assert_eq!(None, cache.may_manage(ServerId(1), UserId(10), &[]));
+    }
+}
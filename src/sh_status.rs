@@ -1,18 +1,45 @@
 use std::collections::{HashMap, HashSet};
-use discord::model::{UserId, OnlineStatus};
-use model::{Tier, StatusReport, UserData, Want, Timeframe};
+use discord::model::{UserId, ServerId, OnlineStatus};
+use model::{Tier, StatusReport, Player, UserData, GuildData, Want, Timeframe, TimeframeKind,
+            TIERS};
 use common::Retain;
 use time;
 use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
+use chrono_tz::Tz;
+use vocabulary::Language;
 
 #[derive(PartialEq, Debug)]
 pub struct ShStatus {
     users_data: HashMap<UserId, UserData>,
+    guilds_data: HashMap<ServerId, GuildData>,
 }
 
 impl ShStatus {
     pub fn new() -> Self {
-        ShStatus { users_data: HashMap::new() }
+        ShStatus {
+            users_data: HashMap::new(),
+            guilds_data: HashMap::new(),
+        }
+    }
+
+    pub fn set_guild_prefix(&mut self, server_id: ServerId, prefix: String) -> &GuildData {
+        let guild_data = self.guilds_data.entry(server_id).or_insert(GuildData::new());
+        guild_data.prefix = Some(prefix);
+        guild_data
+    }
+
+    pub fn get_guild_prefix(&self, server_id: ServerId) -> Option<&str> {
+        self.guilds_data.get(&server_id).and_then(|gd| gd.prefix.as_ref()).map(|p| &**p)
+    }
+
+    pub fn set_guild_language(&mut self, server_id: ServerId, language: Language) -> &GuildData {
+        let guild_data = self.guilds_data.entry(server_id).or_insert(GuildData::new());
+        guild_data.language = Some(language);
+        guild_data
+    }
+
+    pub fn get_guild_language(&self, server_id: ServerId) -> Option<Language> {
+        self.guilds_data.get(&server_id).and_then(|gd| gd.language)
     }
 
     /// Returns new user data.
@@ -21,11 +48,9 @@ impl ShStatus {
                              time: Timeframe,
                              wants: HashSet<Want>)
                              -> &UserData {
-        let user_data = self.users_data.entry(user_id).or_insert(UserData {
-            // TODO set user's actual online status (may theoretically be idle (?))
-            status: OnlineStatus::Online,
-            time_wants: HashMap::new(),
-        });
+        // TODO set user's actual online status (may theoretically be idle (?))
+        let user_data =
+            self.users_data.entry(user_id).or_insert(UserData::new(OnlineStatus::Online));
         {
             let existing_wants = user_data.time_wants.entry(time).or_insert(HashSet::new());
             for want in wants {
@@ -35,17 +60,79 @@ impl ShStatus {
         user_data
     }
 
-    pub fn set_user_doesnt_want_sh(&mut self, user_id: UserId) {
+    /// Removes the user's wants in the given tiers with the given kinds of timeframes, where an
+    /// empty set stands for all of them. Returns the remaining user data, or None if the user never
+    /// wanted to play.
+    pub fn set_user_doesnt_want_sh(&mut self,
+                                   user_id: UserId,
+                                   tiers: &HashSet<Tier>,
+                                   kinds: &HashSet<TimeframeKind>)
+                                   -> Option<&UserData> {
+        let user_data = match self.users_data.get_mut(&user_id) {
+            Some(user_data) => user_data,
+            None => return None,
+        };
+        let mut emptied = Vec::new();
+        for (timeframe, wants) in user_data.time_wants.iter_mut() {
+            if kinds.is_empty() || kinds.contains(&timeframe.kind()) {
+                wants.retain(|want| !tiers.is_empty() && !tiers.contains(&want.tier));
+                if wants.is_empty() {
+                    emptied.push(*timeframe);
+                }
+            }
+        }
+        for timeframe in emptied {
+            user_data.time_wants.remove(&timeframe);
+        }
+        Some(user_data)
+    }
+
+    /// Returns new user data.
+    pub fn set_user_timezone(&mut self, user_id: UserId, timezone: Tz) -> &UserData {
+        // TODO set user's actual online status (may theoretically be idle (?))
+        let user_data =
+            self.users_data.entry(user_id).or_insert(UserData::new(OnlineStatus::Online));
+        user_data.timezone = Some(timezone);
+        user_data
+    }
+
+    /// Removes the user's weekly schedule with the given number (starting at 1, in the order
+    /// they're listed in). Returns the removed schedule and its wants, or None if there's no
+    /// schedule with that number.
+    pub fn remove_user_schedule(&mut self,
+                                user_id: UserId,
+                                number: usize)
+                                -> Option<(Timeframe, HashSet<Want>)> {
+        let user_data = match self.users_data.get_mut(&user_id) {
+            Some(user_data) => user_data,
+            None => return None,
+        };
+        let timeframe = match user_data.schedules().get(number.wrapping_sub(1)) {
+            Some(&(timeframe, _)) => *timeframe,
+            None => return None,
+        };
+        user_data.time_wants.remove(&timeframe).map(|wants| (timeframe, wants))
+    }
+
+    pub fn get_user_data(&self, user_id: UserId) -> Option<&UserData> {
+        self.users_data.get(&user_id)
+    }
+
+    pub fn get_user_timezone(&self, user_id: UserId) -> Option<Tz> {
+        self.users_data.get(&user_id).and_then(|ud| ud.timezone)
+    }
+
+    /// Remembers the user's name to show them by, if the user has data.
+    pub fn set_user_name(&mut self, user_id: UserId, name: &str) {
         if let Some(user_data) = self.users_data.get_mut(&user_id) {
-            user_data.time_wants.clear();
+            if user_data.name.as_ref().map(|n| &**n) != Some(name) {
+                user_data.name = Some(name.to_owned());
+            }
         }
     }
 
     pub fn set_user_changed_status(&mut self, user_id: UserId, status: OnlineStatus) {
-        let user_data = self.users_data.entry(user_id).or_insert(UserData {
-            status: status,
-            time_wants: HashMap::new(),
-        });
+        let user_data = self.users_data.entry(user_id).or_insert(UserData::new(status));
         user_data.status = status;
         if status == OnlineStatus::Offline {
             // User is now offline, delete all wants that were only valid until he logged out.
@@ -54,58 +141,71 @@ impl ShStatus {
     }
 
     pub fn get_current_status(&mut self) -> StatusReport {
+        self.get_status_at(&time::now())
+    }
+
+    fn get_status_at(&mut self, now: &time::Tm) -> StatusReport {
         // Clean up the current user data, e.g. remove outdated wants.
-        update_users_data(self.users_data.values_mut());
-        let update = |mut acc: StatusReport, user_data: &UserData| {
-            if !user_data.time_wants.is_empty() {
-                acc.num_wanting_total += 1;
+        update_users_data(self.users_data.values_mut(), now);
+        let mut report = StatusReport {
+            num_wanting_total: 0,
+            tiers: TIERS.iter().map(|&tier| (tier, Vec::new())).collect(),
+        };
+        for (&user_id, user_data) in self.users_data
+            .iter()
+            .filter(|&(_, ud)| ud.status != OnlineStatus::Offline) {
+            let tz = user_data.timezone_or_utc();
+            // Windows that haven't started yet don't count.
+            let active_wants = user_data.time_wants
+                .iter()
+                .filter(|&(t, _)| t.is_active(now, tz))
+                .collect::<Vec<_>>();
+            if !active_wants.is_empty() {
+                report.num_wanting_total += 1;
             }
-            let (mut wants_t6, mut wants_t8, mut wants_t10) = (false, false, false);
-            for datum in user_data.time_wants.values().flat_map(|s| s.iter()) {
-                match datum.tier {
-                    Tier::Tier6 => wants_t6 = true,
-                    Tier::Tier8 => wants_t8 = true,
-                    Tier::Tier10 => wants_t10 = true,
+            for &mut (tier, ref mut players) in report.tiers.iter_mut() {
+                // The player is up for the tier until the last of their wants in it ends.
+                let until = active_wants.iter()
+                    .filter(|&&(_, wants)| wants.contains(&Want { tier: tier }))
+                    .map(|&(t, _)| t.end(now, tz))
+                    .max();
+                if let Some(until) = until {
+                    players.push(Player {
+                        user_id: user_id,
+                        name: user_data.name.clone(),
+                        status: user_data.status,
+                        until: until,
+                    });
                 }
             }
-            acc.num_wanting_t6 += wants_t6 as usize;
-            acc.num_wanting_t8 += wants_t8 as usize;
-            acc.num_wanting_t10 += wants_t10 as usize;
-            acc
-        };
-        let init_status = StatusReport {
-            num_wanting_total: 0,
-            num_wanting_t6: 0,
-            num_wanting_t8: 0,
-            num_wanting_t10: 0,
-        };
-        self.users_data
-            .values()
-            .filter(|ud| ud.status == OnlineStatus::Online)
-            .fold(init_status, &update)
+        }
+        for &mut (_, ref mut players) in report.tiers.iter_mut() {
+            // Ties are broken by name, so the order doesn't change between requests.
+            players.sort_by(|a, b| {
+                (a.until, &a.name, a.user_id).cmp(&(b.until, &b.name, b.user_id))
+            });
+        }
+        report
     }
 }
 
-fn update_users_data<'a, I: Iterator<Item = &'a mut UserData>>(data: I) {
-    let now = time::now();
+fn update_users_data<'a, I: Iterator<Item = &'a mut UserData>>(data: I, now: &time::Tm) {
     for d in data {
-        // Only retain timespan wants that are valid beyond now.
-        d.time_wants.retain(|&t| {
-            if let Timeframe::Timespan { until } = t {
-                return until > now;
-            }
-            true
-        });
+        // Only retain timespan and window wants that are valid beyond now.
+        d.time_wants.retain(|t| !t.is_expired(now));
     }
 }
 
-const SERIALIZATION_VERSION: u32 = 1;
+/// Version 2 added timezones to the user data, version 3 added windows to the timeframes, version 4
+/// weekly schedules, version 5 guild data, version 6 guild languages and version 7 user names. Data
+/// from older versions can still be decoded.
+const SERIALIZATION_VERSION: u32 = 7;
 
 impl Encodable for ShStatus {
     fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
-        s.emit_seq(2, |s| {
+        s.emit_seq(3, |s| {
             try!(s.emit_seq_elt(0, |s| s.emit_u32(SERIALIZATION_VERSION)));
-            s.emit_seq_elt(1, |s| {
+            try!(s.emit_seq_elt(1, |s| {
                 s.emit_map(self.users_data.len(), |s| {
                     for (i, (k, ref v)) in self.users_data.iter().enumerate() {
                         try!(s.emit_map_elt_key(i, |s| {
@@ -116,6 +216,18 @@ impl Encodable for ShStatus {
                     }
                     Ok(())
                 })
+            }));
+            s.emit_seq_elt(2, |s| {
+                s.emit_map(self.guilds_data.len(), |s| {
+                    for (i, (k, ref v)) in self.guilds_data.iter().enumerate() {
+                        try!(s.emit_map_elt_key(i, |s| {
+                            let ServerId(id) = *k;
+                            s.emit_u64(id)
+                        }));
+                        try!(s.emit_map_elt_val(i, |s| v.encode(s)));
+                    }
+                    Ok(())
+                })
             })
         })
     }
@@ -123,9 +235,9 @@ impl Encodable for ShStatus {
 
 impl Decodable for ShStatus {
     fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
-        d.read_seq(|d, _| {
+        d.read_seq(|d, len| {
             let version = try!(d.read_seq_elt(0, |d| d.read_u32()));
-            if version != SERIALIZATION_VERSION {
+            if version < 1 || version > SERIALIZATION_VERSION {
                 return Err(d.error(&format!("Invalid serialization version {}.", version)));
             }
             let users_data = try!(d.read_seq_elt(1, |d| {
@@ -141,23 +253,240 @@ impl Decodable for ShStatus {
                     Ok(users_data)
                 })
             }));
-            Ok(ShStatus { users_data: users_data })
+            // Data saved before guild data existed only has two elements.
+            let guilds_data = if len > 2 {
+                try!(d.read_seq_elt(2, |d| {
+                    d.read_map(|d, len| {
+                        let mut guilds_data = HashMap::new();
+                        for i in 0..len {
+                            let server_id =
+                                try!(d.read_map_elt_key(i, |d| Ok(ServerId(try!(d.read_u64())))));
+                            let guild_data = try!(d.read_map_elt_val(i, |d| GuildData::decode(d)));
+                            guilds_data.insert(server_id, guild_data);
+                        }
+                        Ok(guilds_data)
+                    })
+                }))
+            } else {
+                HashMap::new()
+            };
+            Ok(ShStatus {
+                users_data: users_data,
+                guilds_data: guilds_data,
+            })
         })
     }
 }
 
 #[cfg(test)]
-mod tests_serialization {
+mod tests_guild_settings {
+    use super::ShStatus;
+    use vocabulary::Language;
+    use discord::model::ServerId;
+
+    #[test]
+    fn prefix() {
+        let mut sh_status = ShStatus::new();
+        assert_eq!(None, sh_status.get_guild_prefix(ServerId(1)));
+        sh_status.set_guild_prefix(ServerId(1), "!sh".to_owned());
+        sh_status.set_guild_prefix(ServerId(1), "?sh".to_owned());
+        assert_eq!(Some("?sh"), sh_status.get_guild_prefix(ServerId(1)));
+        assert_eq!(None, sh_status.get_guild_prefix(ServerId(2)));
+    }
+
+    #[test]
+    fn language() {
+        let mut sh_status = ShStatus::new();
+        sh_status.set_guild_prefix(ServerId(1), "!sh".to_owned());
+        assert_eq!(None, sh_status.get_guild_language(ServerId(1)));
+        sh_status.set_guild_language(ServerId(1), Language::German);
+        assert_eq!(Some(Language::German), sh_status.get_guild_language(ServerId(1)));
+        assert_eq!(Some("!sh"), sh_status.get_guild_prefix(ServerId(1)));
+    }
+}
+
+#[cfg(test)]
+mod tests_current_status {
     use super::ShStatus;
-    use model::{UserData, Want, Timeframe, Tier};
+    use model::{Want, Timeframe, TimeframeKind, Tier, StatusReport, Player, WantEnd};
     use discord::model::{UserId, OnlineStatus};
+    use std::collections::HashSet;
+    use time;
+
+    fn at(sec: i64) -> time::Tm {
+        time::at_utc(time::Timespec::new(sec, 0))
+    }
+
+    fn wants(tiers: &[Tier]) -> HashSet<Want> {
+        tiers.iter().map(|&tier| Want { tier: tier }).collect()
+    }
+
+    /// The total number of players and the numbers for tier 6, 8 and 10.
+    fn counts(report: &StatusReport) -> (usize, usize, usize, usize) {
+        let count = |i: usize| report.tiers[i].1.len();
+        (report.num_wanting_total, count(0), count(1), count(2))
+    }
+
+    fn window(from: i64, until: i64) -> Timeframe {
+        Timeframe::Window {
+            from: at(from),
+            until: at(until),
+        }
+    }
+
+    #[test]
+    fn windows_count_only_while_active() {
+        let mut sh_status = ShStatus::new();
+        sh_status.set_user_wants_sh(UserId(1), window(100, 200), wants(&[Tier::Tier8]));
+        sh_status.set_user_wants_sh(UserId(2), Timeframe::Always, wants(&[Tier::Tier6]));
+
+        let report = sh_status.get_status_at(&at(50));
+        assert_eq!((1, 1, 0, 0),
+                   counts(&report));
+
+        let report = sh_status.get_status_at(&at(150));
+        assert_eq!((2, 1, 1, 0),
+                   counts(&report));
+    }
+
+    #[test]
+    fn players() {
+        let mut sh_status = ShStatus::new();
+        sh_status.set_user_wants_sh(UserId(1), Timeframe::UntilLogout, wants(&[Tier::Tier8]));
+        sh_status.set_user_wants_sh(UserId(1),
+                                    Timeframe::Timespan { until: at(300) },
+                                    wants(&[Tier::Tier6, Tier::Tier8]));
+        sh_status.set_user_name(UserId(1), "Alice");
+        sh_status.set_user_wants_sh(UserId(2), window(100, 200), wants(&[Tier::Tier8]));
+        sh_status.set_user_changed_status(UserId(2), OnlineStatus::Idle);
+        sh_status.set_user_wants_sh(UserId(3), Timeframe::Always, wants(&[Tier::Tier8]));
+        sh_status.set_user_changed_status(UserId(3), OnlineStatus::Offline);
+        // Only users with data get a name.
+        sh_status.set_user_name(UserId(4), "Dave");
+        assert!(sh_status.get_user_data(UserId(4)).is_none());
+
+        let report = sh_status.get_status_at(&at(150));
+        let player = |id, name: Option<&str>, status, until| {
+            Player {
+                user_id: UserId(id),
+                name: name.map(str::to_owned),
+                status: status,
+                until: until,
+            }
+        };
+        let alice = |until| player(1, Some("Alice"), OnlineStatus::Online, until);
+        assert_eq!(vec![(Tier::Tier6, vec![alice(WantEnd::At(at(300)))]),
+                        (Tier::Tier8,
+                         vec![player(2, None, OnlineStatus::Idle, WantEnd::At(at(200))),
+                              alice(WantEnd::Logout)]),
+                        (Tier::Tier10, vec![])],
+                   report.tiers);
+        assert_eq!(2, report.num_wanting_total);
+    }
+
+    #[test]
+    fn weekly_schedules() {
+        // Mondays from 19:00 to 22:00.
+        let schedule = Timeframe::Weekly {
+            days: 1,
+            from: 19 * 60,
+            until: 22 * 60,
+        };
+        let mut sh_status = ShStatus::new();
+        sh_status.set_user_wants_sh(UserId(1), schedule, wants(&[Tier::Tier10]));
+        // 2016-09-26 20:00 UTC, a Monday.
+        let monday_evening = at(1474848000 + 20 * 3600);
+        assert_eq!(1, counts(&sh_status.get_status_at(&monday_evening)).3);
+        assert_eq!(0, counts(&sh_status.get_status_at(&at(1474848000))).3);
+        // Schedules don't expire.
+        assert_eq!(1, sh_status.users_data[&UserId(1)].time_wants.len());
+    }
+
+    #[test]
+    fn remove_schedule() {
+        let schedule = |days, from| {
+            Timeframe::Weekly {
+                days: days,
+                from: from,
+                until: 22 * 60,
+            }
+        };
+        let mut sh_status = ShStatus::new();
+        sh_status.set_user_wants_sh(UserId(1), schedule(1 << 2, 19 * 60), wants(&[Tier::Tier6]));
+        sh_status.set_user_wants_sh(UserId(1), schedule(1, 20 * 60), wants(&[Tier::Tier8]));
+        sh_status.set_user_wants_sh(UserId(1), schedule(1, 19 * 60), wants(&[Tier::Tier10]));
+        sh_status.set_user_wants_sh(UserId(1), Timeframe::Always, wants(&[Tier::Tier10]));
+
+        assert_eq!(None, sh_status.remove_user_schedule(UserId(1), 0));
+        assert_eq!(None, sh_status.remove_user_schedule(UserId(1), 4));
+        assert_eq!(None, sh_status.remove_user_schedule(UserId(2), 1));
+        assert_eq!(Some((schedule(1, 20 * 60), wants(&[Tier::Tier8]))),
+                   sh_status.remove_user_schedule(UserId(1), 2));
+        assert_eq!(Some((schedule(1 << 2, 19 * 60), wants(&[Tier::Tier6]))),
+                   sh_status.remove_user_schedule(UserId(1), 2));
+        assert_eq!(Some((schedule(1, 19 * 60), wants(&[Tier::Tier10]))),
+                   sh_status.remove_user_schedule(UserId(1), 1));
+        assert_eq!(None, sh_status.remove_user_schedule(UserId(1), 1));
+        assert_eq!(1, sh_status.users_data[&UserId(1)].time_wants.len());
+    }
+
+    #[test]
+    fn dont_want_selectively() {
+        let (all_tiers, all_kinds) = (HashSet::new(), HashSet::new());
+        let tier10 = [Tier::Tier10].iter().cloned().collect::<HashSet<Tier>>();
+        let timespans = [TimeframeKind::Timespan].iter().cloned().collect::<HashSet<_>>();
+        let timespan = Timeframe::Timespan { until: at(300) };
+        let mut sh_status = ShStatus::new();
+        sh_status.set_user_wants_sh(UserId(1), Timeframe::Always, wants(&[Tier::Tier8]));
+        sh_status.set_user_wants_sh(UserId(1), timespan, wants(&[Tier::Tier8, Tier::Tier10]));
+
+        assert!(sh_status.set_user_doesnt_want_sh(UserId(2), &all_tiers, &all_kinds).is_none());
+        {
+            let ud = sh_status.set_user_doesnt_want_sh(UserId(1), &tier10, &all_kinds).unwrap();
+            assert_eq!(wants(&[Tier::Tier8]), ud.time_wants[&Timeframe::Always]);
+            assert_eq!(wants(&[Tier::Tier8]), ud.time_wants[&timespan]);
+        }
+        {
+            let ud = sh_status.set_user_doesnt_want_sh(UserId(1), &all_tiers, &timespans).unwrap();
+            assert_eq!(vec![&Timeframe::Always], ud.time_wants.keys().collect::<Vec<_>>());
+        }
+        sh_status.set_user_doesnt_want_sh(UserId(1), &all_tiers, &all_kinds);
+        assert!(sh_status.users_data[&UserId(1)].time_wants.is_empty());
+    }
+
+    #[test]
+    fn expired_windows_are_removed() {
+        let mut sh_status = ShStatus::new();
+        sh_status.set_user_wants_sh(UserId(1), window(100, 200), wants(&[Tier::Tier8]));
+        sh_status.set_user_wants_sh(UserId(1),
+                                    Timeframe::Timespan { until: at(300) },
+                                    wants(&[Tier::Tier10]));
+
+        let report = sh_status.get_status_at(&at(200));
+        assert_eq!((1, 0, 0, 1),
+                   counts(&report));
+        assert_eq!(1, sh_status.users_data[&UserId(1)].time_wants.len());
+
+        let report = sh_status.get_status_at(&at(300));
+        assert_eq!(0, report.num_wanting_total);
+        assert!(sh_status.users_data[&UserId(1)].time_wants.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_serialization {
+    use super::ShStatus;
+    use model::{UserData, GuildData, Want, Timeframe, Tier};
+    use vocabulary::Language;
+    use discord::model::{UserId, ServerId, OnlineStatus};
     use std::collections::{HashMap, HashSet};
     use rustc_serialize::json::{encode, decode};
+    use chrono_tz::Tz;
     use time;
 
     #[test]
     fn sh_status_empty() {
-        let sh_status = ShStatus { users_data: HashMap::new() };
+        let sh_status = ShStatus::new();
         let encoded = encode(&sh_status).unwrap();
         let decoded = decode::<ShStatus>(&encoded).unwrap();
         assert_eq!(sh_status, decoded);
@@ -165,16 +494,14 @@ mod tests_serialization {
 
     #[test]
     fn sh_status_simple() {
-        let empty_user_data = UserData {
-            status: OnlineStatus::Online,
-            time_wants: HashMap::new(),
-        };
+        let empty_user_data = UserData::new(OnlineStatus::Online);
         let sh_status = ShStatus {
             users_data: {
                 let mut users_data = HashMap::new();
                 users_data.insert(UserId(0), empty_user_data);
                 users_data
             },
+            guilds_data: HashMap::new(),
         };
         let encoded = encode(&sh_status).unwrap();
         let decoded = decode::<ShStatus>(&encoded).unwrap();
@@ -213,13 +540,47 @@ mod tests_serialization {
                 let user_data = UserData {
                     status: status,
                     time_wants: time_wants,
+                    timezone: Some(Tz::America__Los_Angeles),
+                    name: Some(format!("Player {}", user_id.0)),
                 };
                 users_data.insert(*user_id, user_data);
             }
-            ShStatus { users_data: users_data }
+            let mut guilds_data = HashMap::new();
+            guilds_data.insert(ServerId(1), GuildData::new());
+            guilds_data.insert(ServerId(2),
+                               GuildData {
+                                   prefix: Some("!sh".to_owned()),
+                                   language: Some(Language::German),
+                               });
+            ShStatus {
+                users_data: users_data,
+                guilds_data: guilds_data,
+            }
         };
         let encoded = encode(&sh_status).unwrap();
         let decoded = decode::<ShStatus>(&encoded).unwrap();
         assert_eq!(sh_status, decoded);
     }
+
+    #[test]
+    fn sh_status_version_1() {
+        let decoded = decode::<ShStatus>("[1,{\"1357\":[\"Online\",{\"UntilLogout\":[8]}]}]")
+            .unwrap();
+        let mut user_data = UserData::new(OnlineStatus::Online);
+        user_data.time_wants.insert(Timeframe::UntilLogout,
+                                    vec![Want { tier: Tier::Tier8 }].into_iter().collect());
+        let mut users_data = HashMap::new();
+        users_data.insert(UserId(1357), user_data);
+        assert_eq!(ShStatus {
+                       users_data: users_data,
+                       guilds_data: HashMap::new(),
+                   },
+                   decoded);
+    }
+
+    #[test]
+    fn sh_status_unknown_version() {
+        assert!(decode::<ShStatus>("[0,{}]").is_err());
+        assert!(decode::<ShStatus>("[8,{}]").is_err());
+    }
 }
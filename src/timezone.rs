@@ -0,0 +1,131 @@
+use chrono::{DateTime, TimeZone, NaiveDate, Datelike, Timelike};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use time;
+
+/// Looks up a timezone by its name in the tz database (e.g. "Europe/Berlin"), ignoring case.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    TZ_VARIANTS.iter()
+        .find(|tz| tz.name().eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| {
+            format!("Unknown timezone \"{}\". Use a name from the tz database, e.g. \
+                     Europe/Berlin or America/New_York.",
+                    name)
+        })
+}
+
+/// Whether the point in time can be converted to local time in every timezone, which chrono can
+/// only do up to the year 262143 or so.
+pub fn in_range(tm: &time::Tm) -> bool {
+    // Leaves a day of room for the offsets of timezones.
+    let sec = tm.to_timespec().sec;
+    DateTime::from_timestamp(sec.saturating_add(86400), 0).is_some() &&
+    DateTime::from_timestamp(sec.saturating_sub(86400), 0).is_some()
+}
+
+/// Returns the point in time in the timezone, or None if it's out of chrono's range (see
+/// `in_range()`).
+fn to_local(tm: &time::Tm, tz: Tz) -> Option<DateTime<Tz>> {
+    tz.timestamp_opt(tm.to_timespec().sec, 0).single()
+}
+
+/// Returns the date in the timezone at the given point in time, or None if it's out of range.
+pub fn local_date(tm: &time::Tm, tz: Tz) -> Option<NaiveDate> {
+    to_local(tm, tz).map(|local| local.naive_local().date())
+}
+
+/// Returns the day of the week (0 is Monday) and the minutes since midnight in the timezone at the
+/// given point in time, or None if it's out of range.
+pub fn local_weekday_and_minutes(tm: &time::Tm, tz: Tz) -> Option<(u32, u32)> {
+    to_local(tm, tz).map(|local| {
+        (local.weekday().num_days_from_monday(), local.hour() * 60 + local.minute())
+    })
+}
+
+/// Returns the point in time at which it is the given time of day on the date in the timezone.
+/// Returns None if that time doesn't exist, e.g. because it's skipped by a daylight saving time
+/// change. If it exists twice, the earlier one is returned.
+pub fn from_local(date: NaiveDate, hours: u32, minutes: u32, tz: Tz) -> Option<time::Tm> {
+    date.and_hms_opt(hours, minutes, 0)
+        .and_then(|naive| tz.from_local_datetime(&naive).earliest())
+        .map(|local| time::at_utc(time::Timespec::new(local.timestamp(), 0)))
+}
+
+/// Formats a point in time as it is in the timezone. The format is the same as for
+/// `time::strftime()`, "%Z" is the abbreviated timezone name (e.g. CEST). A point in time that's
+/// out of range is formatted in UTC as RFC 3339 instead.
+pub fn format(tm: &time::Tm, tz: Tz, format: &str) -> String {
+    match to_local(tm, tz) {
+        Some(local) => local.format(format).to_string(),
+        None => tm.rfc3339().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests_timezone {
+    use super::{parse_timezone, in_range, local_date, local_weekday_and_minutes, from_local,
+                format};
+    use chrono::NaiveDate;
+    use chrono_tz::Tz;
+    use time;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Tz::Europe__Berlin), parse_timezone("Europe/Berlin"));
+        assert_eq!(Ok(Tz::America__New_York), parse_timezone("america/new_york"));
+        assert_eq!(Ok(Tz::UTC), parse_timezone("UTC"));
+        assert!(parse_timezone("Europe/Nowhere").is_err());
+        assert!(parse_timezone("").is_err());
+    }
+
+    #[test]
+    fn date() {
+        // 2016-09-27 23:30 UTC
+        let tm = time::at_utc(time::Timespec::new(1475019000, 0));
+        assert_eq!(NaiveDate::from_ymd_opt(2016, 9, 27), local_date(&tm, Tz::UTC));
+        assert_eq!(NaiveDate::from_ymd_opt(2016, 9, 28), local_date(&tm, Tz::Europe__Berlin));
+        assert_eq!(NaiveDate::from_ymd_opt(2016, 9, 27), local_date(&tm, Tz::America__New_York));
+    }
+
+    #[test]
+    fn weekday_and_minutes() {
+        // 2016-09-27 23:30 UTC, a Tuesday
+        let tm = time::at_utc(time::Timespec::new(1475019000, 0));
+        assert_eq!(Some((1, 23 * 60 + 30)), local_weekday_and_minutes(&tm, Tz::UTC));
+        assert_eq!(Some((2, 60 + 30)), local_weekday_and_minutes(&tm, Tz::Europe__Berlin));
+    }
+
+    #[test]
+    fn out_of_range() {
+        // Beyond the year 262143.
+        let tm = time::at_utc(time::Timespec::new(9223372036854, 0));
+        assert!(!in_range(&tm));
+        assert_eq!(None, local_date(&tm, Tz::Europe__Berlin));
+        assert_eq!(None, local_weekday_and_minutes(&tm, Tz::UTC));
+        assert_eq!(tm.rfc3339().to_string(), format(&tm, Tz::Europe__Berlin, "%R %Z"));
+        assert!(in_range(&time::at_utc(time::Timespec::new(1475008200, 0))));
+    }
+
+    #[test]
+    fn local_time() {
+        let date = NaiveDate::from_ymd_opt(2016, 9, 27).unwrap();
+        // 2016-09-27 20:30 UTC
+        assert_eq!(Some(time::at_utc(time::Timespec::new(1475008200, 0))),
+                   from_local(date, 22, 30, Tz::Europe__Berlin));
+        assert_eq!(Some(time::at_utc(time::Timespec::new(1475015400, 0))),
+                   from_local(date, 22, 30, Tz::UTC));
+        assert_eq!(None, from_local(date, 24, 0, Tz::UTC));
+        // Skipped by the switch to daylight saving time.
+        let date = NaiveDate::from_ymd_opt(2016, 3, 27).unwrap();
+        assert_eq!(None, from_local(date, 2, 30, Tz::Europe__Berlin));
+    }
+
+    #[test]
+    fn formatting() {
+        // 2016-09-27 20:30 UTC
+        let tm = time::at_utc(time::Timespec::new(1475008200, 0));
+        assert_eq!("22:30 CEST", format(&tm, Tz::Europe__Berlin, "%R %Z"));
+        assert_eq!("20:30 UTC", format(&tm, Tz::UTC, "%R %Z"));
+        assert_eq!("2016-09-27 16:30 EDT", format(&tm, Tz::America__New_York, "%F %R %Z"));
+    }
+}
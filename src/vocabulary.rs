@@ -0,0 +1,240 @@
+/// A language the command keywords can be written in.
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum Language {
+    English,
+    German,
+}
+
+pub const LANGUAGES: [Language; 2] = [Language::English, Language::German];
+
+impl Language {
+    /// The name of the language in the language itself.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    /// The ISO 639-1 code of the language, used when saving it.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+
+    /// Looks up a language by its code, its English name or its name in the language itself,
+    /// ignoring case.
+    pub fn parse(s: &str) -> Result<Language, String> {
+        match &*s.to_lowercase() {
+            "en" | "english" => Ok(Language::English),
+            "de" | "german" | "deutsch" => Ok(Language::German),
+            _ => {
+                Err(format!("Unknown language \"{}\", expected one of {}.",
+                            s,
+                            LANGUAGES.iter()
+                                .map(|l| l.name())
+                                .collect::<Vec<&str>>()
+                                .join(", ")))
+            }
+        }
+    }
+}
+
+/// The words a message can start with (and "dont", which comes before "want"), and the ones
+/// commands take as arguments (e.g. "remove" in "schedules remove 2").
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum Keyword {
+    Help,
+    Want,
+    Dont,
+    Status,
+    Me,
+    Timezone,
+    Schedules,
+    Prefix,
+    Language,
+    Remove,
+}
+
+/// A keyword, a language and the words for the keyword in the language.
+type Entry = (Keyword, Language, &'static [&'static str]);
+
+/// The words for each keyword in each language. The first word of a keyword in a language is the
+/// one shown in replies, the others are aliases.
+const WORDS: &'static [Entry] =
+    &[(Keyword::Help, Language::English, &["help", "?"]),
+      (Keyword::Want, Language::English, &["want", "w"]),
+      (Keyword::Dont, Language::English, &["dont", "don't"]),
+      (Keyword::Status, Language::English, &["status", "st", "who"]),
+      (Keyword::Me, Language::English, &["me", "whoami"]),
+      (Keyword::Timezone, Language::English, &["timezone", "tz"]),
+      (Keyword::Schedules, Language::English, &["schedules", "schedule"]),
+      (Keyword::Prefix, Language::English, &["prefix"]),
+      (Keyword::Language, Language::English, &["language", "lang"]),
+      (Keyword::Remove, Language::English, &["remove", "delete"]),
+      (Keyword::Help, Language::German, &["hilfe", "?"]),
+      (Keyword::Want, Language::German, &["will", "möchte", "w"]),
+      (Keyword::Dont, Language::German, &["nicht"]),
+      (Keyword::Status, Language::German, &["status", "st", "wer"]),
+      (Keyword::Me, Language::German, &["ich"]),
+      (Keyword::Timezone, Language::German, &["zeitzone", "tz"]),
+      (Keyword::Schedules, Language::German, &["zeitpläne", "zeitplan"]),
+      (Keyword::Prefix, Language::German, &["präfix", "prefix"]),
+      (Keyword::Language, Language::German, &["sprache"]),
+      (Keyword::Remove, Language::German, &["entfernen", "löschen"])];
+
+/// The keywords understood in a guild: those of its language, plus the English ones so commands
+/// from the documentation always work.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Vocabulary {
+    /// In order of preference, the first one is used in replies.
+    languages: Vec<Language>,
+}
+
+impl Vocabulary {
+    pub fn new(language: Language) -> Self {
+        let mut languages = vec![language];
+        if language != Language::English {
+            languages.push(Language::English);
+        }
+        Vocabulary { languages: languages }
+    }
+
+    /// A vocabulary with all languages, for when there's no guild to take the language from.
+    pub fn all() -> Self {
+        Vocabulary { languages: LANGUAGES.to_vec() }
+    }
+
+    pub fn language(&self) -> Language {
+        self.languages[0]
+    }
+
+    /// Looks up the keyword a word stands for, ignoring case.
+    pub fn keyword(&self, word: &str) -> Option<Keyword> {
+        let word = word.to_lowercase();
+        self.entries()
+            .into_iter()
+            .find(|&&(_, _, words)| words.contains(&&*word))
+            .map(|&(keyword, _, _)| keyword)
+    }
+
+    /// Returns the word for the keyword shown in replies.
+    pub fn word(&self, keyword: Keyword) -> &'static str {
+        self.words(keyword)[0]
+    }
+
+    /// Returns all words for the keyword, those of the preferred language first.
+    pub fn words(&self, keyword: Keyword) -> Vec<&'static str> {
+        let mut all_words: Vec<&'static str> = Vec::new();
+        for &(_, _, words) in self.entries().into_iter().filter(|&&(k, _, _)| k == keyword) {
+            for word in words {
+                if !all_words.contains(word) {
+                    all_words.push(word);
+                }
+            }
+        }
+        all_words
+    }
+
+    /// Returns the words for all keywords, those of the preferred language first.
+    pub fn all_words(&self) -> Vec<&'static str> {
+        let mut all_words: Vec<&'static str> = Vec::new();
+        for &(_, _, words) in self.entries() {
+            for word in words {
+                if !all_words.contains(word) {
+                    all_words.push(word);
+                }
+            }
+        }
+        all_words
+    }
+
+    /// Returns the entries of the vocabulary's languages, in order of preference.
+    fn entries(&self) -> Vec<&'static Entry> {
+        self.languages
+            .iter()
+            .flat_map(|&language| WORDS.iter().filter(move |&&(_, l, _)| l == language))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests_vocabulary {
+    use super::{Vocabulary, Keyword, Language, WORDS, LANGUAGES};
+
+    #[test]
+    fn every_keyword_in_every_language() {
+        let keywords = [Keyword::Help,
+                        Keyword::Want,
+                        Keyword::Dont,
+                        Keyword::Status,
+                        Keyword::Me,
+                        Keyword::Timezone,
+                        Keyword::Schedules,
+                        Keyword::Prefix,
+                        Keyword::Language,
+                        Keyword::Remove];
+        for &language in LANGUAGES.iter() {
+            for &keyword in keywords.iter() {
+                let count =
+                    WORDS.iter().filter(|&&(k, l, _)| k == keyword && l == language).count();
+                assert_eq!(1, count, "{:?} in {:?}", keyword, language);
+            }
+        }
+    }
+
+    #[test]
+    fn words_are_lowercase_and_unambiguous() {
+        for &(keyword, language, words) in WORDS {
+            for word in words {
+                assert_eq!(word.to_lowercase(), *word);
+                assert_eq!(Some(keyword),
+                           Vocabulary::new(language).keyword(word),
+                           "{} in {:?}",
+                           word,
+                           language);
+                assert_eq!(Some(keyword), Vocabulary::all().keyword(word), "{}", word);
+            }
+        }
+    }
+
+    #[test]
+    fn english() {
+        let vocabulary = Vocabulary::new(Language::English);
+        assert_eq!(Some(Keyword::Want), vocabulary.keyword("Want"));
+        assert_eq!(Some(Keyword::Status), vocabulary.keyword("st"));
+        assert_eq!(Some(Keyword::Status), vocabulary.keyword("Who"));
+        assert_eq!(Some(Keyword::Help), vocabulary.keyword("?"));
+        assert_eq!(None, vocabulary.keyword("will"));
+        assert_eq!("want", vocabulary.word(Keyword::Want));
+        assert_eq!(vec!["want", "w"], vocabulary.words(Keyword::Want));
+        assert!(vocabulary.all_words().contains(&"timezone"));
+        assert!(!vocabulary.all_words().contains(&"hilfe"));
+    }
+
+    #[test]
+    fn german_falls_back_to_english() {
+        let vocabulary = Vocabulary::new(Language::German);
+        assert_eq!(Some(Keyword::Want), vocabulary.keyword("will"));
+        assert_eq!(Some(Keyword::Want), vocabulary.keyword("MÖCHTE"));
+        assert_eq!(Some(Keyword::Want), vocabulary.keyword("want"));
+        assert_eq!(Some(Keyword::Dont), vocabulary.keyword("nicht"));
+        assert_eq!(Some(Keyword::Help), vocabulary.keyword("hilfe"));
+        assert_eq!("will", vocabulary.word(Keyword::Want));
+        assert_eq!(vec!["will", "möchte", "w", "want"], vocabulary.words(Keyword::Want));
+        assert_eq!(Some(Keyword::Remove), vocabulary.keyword("Löschen"));
+        assert_eq!(Some(Keyword::Remove), vocabulary.keyword("remove"));
+        assert_eq!("entfernen", vocabulary.word(Keyword::Remove));
+    }
+
+    #[test]
+    fn parse_language() {
+        assert_eq!(Ok(Language::German), Language::parse("Deutsch"));
+        assert_eq!(Ok(Language::German), Language::parse("de"));
+        assert_eq!(Ok(Language::English), Language::parse("english"));
+        assert_eq!(Err("Unknown language \"fr\", expected one of English, Deutsch.".to_owned()),
+                   Language::parse("fr"));
+    }
+}